@@ -0,0 +1,386 @@
+use std::fmt::Display;
+
+use crate::{
+    parser::{ParseError, Parser},
+    tokenizer::{tokenize, TokenizeError},
+    tokens::{Number, Operation, Paren, Spanned, Token, UnaryOperation},
+    TokenVisitor,
+};
+
+/// Why postfix tokens make no tree. There is no source string behind them,
+/// so positions are token indices, not byte offsets
+#[derive(Debug, PartialEq, Eq)]
+pub enum TreeError {
+    /// Operator or call at this index lacks operands
+    NotEnoughArgs(usize),
+    /// More than one expression is left after the token at this index
+    NotEnoughOps(usize),
+    /// Paren, comma or colon at this index, postfix form has none of them
+    UnexpectedToken(usize),
+    /// Nothing to build an expression from
+    Empty,
+}
+
+impl Display for TreeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TreeError::NotEnoughArgs(idx) => f.write_fmt(format_args!("token #{idx} lacks arguments")),
+            TreeError::NotEnoughOps(idx) => f.write_fmt(format_args!("arguments without operator by token #{idx}")),
+            TreeError::UnexpectedToken(idx) => f.write_fmt(format_args!("token #{idx} has no place in postfix form")),
+            TreeError::Empty => f.write_str("empty expression"),
+        }
+    }
+}
+
+impl std::error::Error for TreeError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Num(Number),
+    Var(String),
+    Unary { op: UnaryOperation, arg: Box<Expr> },
+    Binary { op: Operation, lhs: Box<Expr>, rhs: Box<Expr> },
+    Call { name: String, args: Vec<Expr> },
+    /// `cond ? then : otherwise`
+    Cond { cond: Box<Expr>, then: Box<Expr>, otherwise: Box<Expr> },
+    /// Explicit parentheses, kept by [`Expr::from_infix`]. Postfix form has no place
+    /// for them, so [`Expr::from_rpn`] never produces them and [`Expr::to_rpn`] drops them
+    Group(Box<Expr>),
+}
+
+impl Expr {
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        Self::from_infix(tokenize(input))
+    }
+
+    /// Builds the tree from infix tokens, as produced by [`crate::tokenizer::Tokenizer`],
+    /// explicit parentheses become [`Expr::Group`]
+    pub fn from_infix<I>(tokens: I) -> Result<Self, ParseError>
+    where
+        I: Iterator<Item = Result<Spanned<Token>, TokenizeError>>,
+    {
+        let mut parser = Parser::new(tokens);
+        let rpn = parser.by_ref().collect::<Result<Vec<_>, _>>()?;
+        // the parser has checked the arguments of every operator, only emptiness is left
+        Self::build(rpn, parser.groups()).map_err(|err| match err {
+            TreeError::Empty => ParseError::Empty,
+            _ => ParseError::NotEnoughArgs(parser.span()),
+        })
+    }
+
+    /// Builds the tree from postfix tokens, as produced by [`Parser`]
+    pub fn from_rpn<I: IntoIterator<Item = Token>>(tokens: I) -> Result<Self, TreeError> {
+        Self::build(tokens, &[])
+    }
+
+    /// `groups` are counts of tokens after which the last expression was in parens
+    fn build<I: IntoIterator<Item = Token>>(tokens: I, groups: &[usize]) -> Result<Self, TreeError> {
+        let mut builder = Builder { stack: vec![], idx: 0, error: None };
+        let mut groups = groups.iter().peekable();
+        for (idx, tok) in tokens.into_iter().enumerate() {
+            builder.idx = idx;
+            builder.visit_token(tok);
+            if let Some(err) = builder.error.take() {
+                return Err(err);
+            }
+            while groups.next_if(|&&end| end <= idx + 1).is_some() {
+                if let Some(inner) = builder.stack.pop() {
+                    builder.stack.push(Expr::Group(Box::new(inner)));
+                }
+            }
+        }
+        match builder.stack.len() {
+            0 => Err(TreeError::Empty),
+            1 => Ok(builder.stack.pop().unwrap()),
+            _ => Err(TreeError::NotEnoughOps(builder.idx)),
+        }
+    }
+
+    /// Same tree without [`Expr::Group`], printed with only the parentheses it needs
+    pub fn ungrouped(self) -> Self {
+        let ungroup = |expr: Box<Expr>| Box::new(expr.ungrouped());
+        match self {
+            Expr::Group(inner) => inner.ungrouped(),
+            Expr::Unary { op, arg } => Expr::Unary { op, arg: ungroup(arg) },
+            Expr::Binary { op, lhs, rhs } => Expr::Binary { op, lhs: ungroup(lhs), rhs: ungroup(rhs) },
+            Expr::Call { name, args } => Expr::Call { name, args: args.into_iter().map(Expr::ungrouped).collect() },
+            Expr::Cond { cond, then, otherwise } => {
+                Expr::Cond { cond: ungroup(cond), then: ungroup(then), otherwise: ungroup(otherwise) }
+            }
+            atom => atom,
+        }
+    }
+
+    /// Postfix form of the tree, the same [`Parser`] would produce
+    pub fn to_rpn(&self) -> Vec<Token> {
+        let mut out = vec![];
+        self.push_rpn(&mut out);
+        out
+    }
+
+    fn push_rpn(&self, out: &mut Vec<Token>) {
+        match self {
+            Expr::Num(num) => out.push(Token::Number(num.clone())),
+            Expr::Var(name) => out.push(Token::Ident(name.clone())),
+            Expr::Unary { op, arg } => {
+                arg.push_rpn(out);
+                out.push(Token::UnaryOper(op.clone()));
+            }
+            Expr::Binary { op, lhs, rhs } => {
+                lhs.push_rpn(out);
+                rhs.push_rpn(out);
+                out.push(Token::Oper(op.clone()));
+            }
+            Expr::Call { name, args } => {
+                args.iter().for_each(|arg| arg.push_rpn(out));
+                out.push(Token::Call { name: name.clone(), arity: args.len() });
+            }
+            Expr::Cond { cond, then, otherwise } => {
+                cond.push_rpn(out);
+                then.push_rpn(out);
+                otherwise.push_rpn(out);
+                out.push(Token::Question);
+            }
+            Expr::Group(inner) => inner.push_rpn(out),
+        }
+    }
+}
+
+impl Expr {
+    /// How tight it binds, atoms can't be split by any operator
+    fn prio(&self) -> i32 {
+        match self {
+            Expr::Binary { op, .. } => op.prio(),
+            Expr::Unary { op, .. } => op.prio(),
+            // below any operator
+            Expr::Cond { .. } => 0,
+            _ if self.is_prefixed() => UnaryOperation::Neg.prio(),
+            Expr::Num(_) | Expr::Var(_) | Expr::Call { .. } | Expr::Group(_) => i32::MAX,
+        }
+    }
+
+    /// Starts with a prefix operator, negative numbers read back as unary minus
+    fn is_prefixed(&self) -> bool {
+        match self {
+            Expr::Unary { .. } => true,
+            Expr::Num(num) => num.to_string().starts_with('-'),
+            _ => false,
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut std::fmt::Formatter<'_>, parens: bool) -> std::fmt::Result {
+        if parens {
+            f.write_fmt(format_args!("({self})"))
+        } else {
+            f.write_fmt(format_args!("{self}"))
+        }
+    }
+}
+
+/// Infix form with only the parentheses that precedence and associativity
+/// require, besides explicit [`Expr::Group`] ones
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Num(num) => f.write_fmt(format_args!("{num}")),
+            Expr::Var(name) => f.write_str(name),
+            Expr::Unary { op, arg } => {
+                f.write_str(op.symbol())?;
+                if op.symbol().ends_with(char::is_alphanumeric) {
+                    // a word, like the custom `neg x`
+                    f.write_str(" ")?;
+                }
+                // prefix operators nest, `--x` needs nothing
+                let parens = !arg.is_prefixed() && arg.prio() < op.prio();
+                arg.fmt_operand(f, parens)
+            }
+            Expr::Binary { op, lhs, rhs } => {
+                let lhs_parens = lhs.prio() < op.prio() || (lhs.prio() == op.prio() && op.is_right_assoc());
+                lhs.fmt_operand(f, lhs_parens)?;
+                f.write_fmt(format_args!(" {op} "))?;
+                // prefix operator takes everything after it anyway
+                let rhs_parens = !rhs.is_prefixed()
+                    && (rhs.prio() < op.prio() || (rhs.prio() == op.prio() && !op.is_right_assoc()));
+                rhs.fmt_operand(f, rhs_parens)
+            }
+            Expr::Call { name, args } => {
+                f.write_fmt(format_args!("{name}("))?;
+                for (idx, arg) in args.iter().enumerate() {
+                    if idx != 0 {
+                        f.write_str(", ")?;
+                    }
+                    f.write_fmt(format_args!("{arg}"))?;
+                }
+                f.write_str(")")
+            }
+            Expr::Cond { cond, then, otherwise } => {
+                // the rest are delimited by `?` and `:` or right-associative
+                cond.fmt_operand(f, cond.prio() <= self.prio())?;
+                f.write_fmt(format_args!(" ? {then} : {otherwise}"))
+            }
+            Expr::Group(inner) => f.write_fmt(format_args!("({inner})")),
+        }
+    }
+}
+
+struct Builder {
+    stack: Vec<Expr>,
+    /// Of the current token
+    idx: usize,
+    error: Option<TreeError>,
+}
+
+impl Builder {
+    fn pop_args(&mut self, count: usize) -> Option<Vec<Expr>> {
+        match self.stack.len().checked_sub(count) {
+            Some(start) => Some(self.stack.split_off(start)),
+            None => {
+                self.error = Some(TreeError::NotEnoughArgs(self.idx));
+                None
+            }
+        }
+    }
+}
+
+impl TokenVisitor for Builder {
+    fn visit_paren(&mut self, _paren: Paren) {
+        self.error = Some(TreeError::UnexpectedToken(self.idx));
+    }
+
+    fn visit_op(&mut self, op: Operation) {
+        if let Some([lhs, rhs]) = self.pop_args(2).and_then(|args| <[Expr; 2]>::try_from(args).ok()) {
+            self.stack.push(Expr::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs) });
+        }
+    }
+
+    fn visit_unary(&mut self, op: UnaryOperation) {
+        if let Some(arg) = self.pop_args(1).and_then(|mut args| args.pop()) {
+            self.stack.push(Expr::Unary { op, arg: Box::new(arg) });
+        }
+    }
+
+    fn visit_num(&mut self, num: Number) {
+        self.stack.push(Expr::Num(num));
+    }
+
+    fn visit_ident(&mut self, name: String) {
+        self.stack.push(Expr::Var(name));
+    }
+
+    fn visit_comma(&mut self) {
+        self.error = Some(TreeError::UnexpectedToken(self.idx));
+    }
+
+    fn visit_call(&mut self, name: String, arity: usize) {
+        if let Some(args) = self.pop_args(arity) {
+            self.stack.push(Expr::Call { name, args });
+        }
+    }
+
+    fn visit_question(&mut self) {
+        if let Some([cond, then, otherwise]) = self.pop_args(3).and_then(|args| <[Expr; 3]>::try_from(args).ok()) {
+            self.stack.push(Expr::Cond { cond: Box::new(cond), then: Box::new(then), otherwise: Box::new(otherwise) });
+        }
+    }
+
+    fn visit_colon(&mut self) {
+        self.error = Some(TreeError::UnexpectedToken(self.idx));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parser::{parse, ParseError},
+        tokens::{Number, Operation, Span, Token, UnaryOperation},
+    };
+
+    use super::{Expr, TreeError};
+
+    fn num(num: i32) -> Box<Expr> {
+        Box::new(Expr::Num(Number::Int(num.into())))
+    }
+
+    #[test]
+    fn round_trip() {
+        for input in ["1 + 2 * 3", "-(x - 1) ^ 2 ^ y", "max(1, min(a, 2), abs(-b)) // 3", "f()", "a ? b : c ? 1 : 2"] {
+            let rpn: Vec<_> = parse(input).collect::<Result<_, _>>().unwrap();
+            let expr = Expr::from_rpn(rpn.clone()).unwrap();
+            assert_eq!(expr.to_rpn(), rpn, "{input}");
+        }
+    }
+
+    #[test]
+    fn tree() {
+        let sub = Expr::Binary { op: Operation::Sub, lhs: num(1), rhs: num(2) };
+        assert_eq!(
+            Expr::parse("(1 - 2) * 3"),
+            Ok(Expr::Binary { op: Operation::Mul, lhs: Box::new(Expr::Group(Box::new(sub.clone()))), rhs: num(3) })
+        );
+        assert_eq!(
+            Expr::parse("max(((1 - 2)), 3)"),
+            Ok(Expr::Call {
+                name: "max".to_owned(),
+                args: vec![Expr::Group(Box::new(Expr::Group(Box::new(sub.clone())))), *num(3)],
+            })
+        );
+        let neg = Expr::Unary { op: UnaryOperation::Neg, arg: Box::new(sub) };
+        assert_eq!(Expr::parse("-(1 - 2)").map(Expr::ungrouped), Ok(neg));
+        let grouped = Expr::Binary { op: Operation::Mul, lhs: Box::new(Expr::Group(num(1))), rhs: num(3) };
+        assert_eq!(
+            grouped.to_rpn(),
+            vec![
+                Token::Number(Number::Int(1.into())),
+                Token::Number(Number::Int(3.into())),
+                Token::Oper(Operation::Mul),
+            ]
+        );
+    }
+
+    #[test]
+    fn infix() {
+        for (input, expected) in [
+            ("((1 + (2 * 3)))", "1 + 2 * 3"),
+            ("(1 + 2) * 3", "(1 + 2) * 3"),
+            ("(1 - 2) - (3 - 4)", "1 - 2 - (3 - 4)"),
+            ("(2 ^ 3) ^ 4 ^ (5)", "(2 ^ 3) ^ 4 ^ 5"),
+            ("-(2 ^ 2) + (-2) ^ 2", "-2 ^ 2 + (-2) ^ 2"),
+            ("-(x + 1) * -(-y)", "-(x + 1) * --y"),
+            ("2 ^ (-3 * 4)", "2 ^ (-3 * 4)"),
+            ("max((1), (a // b) % 2, f())", "max(1, a // b % 2, f())"),
+            ("(a > 1 ? (a ? b : c) : (d ? e : f)) + 1", "(a > 1 ? a ? b : c : d ? e : f) + 1"),
+            ("((a ? b : c) ? d : e)", "(a ? b : c) ? d : e"),
+            ("-(a ? 1 : 2) * if(a, 1, 2)", "-(a ? 1 : 2) * if(a, 1, 2)"),
+            ("((a & b) | (c xor d)) & (e << (1 + f))", "(a & b | c xor d) & e << 1 + f"),
+            ("~(a >> 2) == (~a) >> 2", "~(a >> 2) == ~a >> 2"),
+        ] {
+            let expr = Expr::parse(input).unwrap();
+            // explicit parens are kept as they are
+            assert_eq!(expr.to_string(), input, "{input}");
+            let expr = expr.ungrouped();
+            assert_eq!(expr.to_string(), expected, "{input}");
+            assert_eq!(Expr::parse(expected).map(Expr::ungrouped), Ok(expr), "{expected}");
+        }
+        let grouped = Expr::Binary { op: Operation::Mul, lhs: Box::new(Expr::Group(num(1))), rhs: num(3) };
+        assert_eq!(grouped.to_string(), "(1) * 3");
+        let negative = Expr::Binary { op: Operation::Pow, lhs: num(-2), rhs: num(-2) };
+        assert_eq!(negative.to_string(), "(-2) ^ -2");
+    }
+
+    #[test]
+    fn failures() {
+        assert_eq!(Expr::parse(""), Err(ParseError::Empty));
+        // spans of the source string
+        assert_eq!(Expr::parse("  12 + 34 -"), Err(ParseError::NotEnoughArgs(Span::new(10, 11))));
+        assert_eq!(Expr::parse("1 ? 2 :"), Err(ParseError::NotEnoughArgs(Span::new(6, 7))));
+        assert_eq!(Expr::parse("(1"), Err(ParseError::UnmatchedParens(Span::new(0, 1))));
+
+        assert_eq!(Expr::from_rpn([Token::Oper(Operation::Add)]), Err(TreeError::NotEnoughArgs(0)));
+        assert_eq!(
+            Expr::from_rpn([Token::Number(Number::Int(1.into())), Token::Ident("x".to_owned())]),
+            Err(TreeError::NotEnoughOps(1))
+        );
+        assert_eq!(Expr::from_rpn([Token::Comma]), Err(TreeError::UnexpectedToken(0)));
+        assert_eq!(Expr::from_rpn([]), Err(TreeError::Empty));
+    }
+}
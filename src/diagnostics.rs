@@ -0,0 +1,203 @@
+use std::fmt::Display;
+
+use crate::{
+    parser::ParseError,
+    tokenizer::{TokenizeError, TokenizeErrorKind},
+    tokens::{Position, Span},
+};
+
+/// Error rendered against its source: the line with the bad span underlined
+/// and an optional hint, in the spirit of compiler messages
+#[derive(Debug)]
+pub struct Diagnostic<'s> {
+    source: &'s str,
+    message: String,
+    span: Option<Span>,
+    help: Option<&'static str>,
+}
+
+impl<'s> Diagnostic<'s> {
+    pub fn tokenize_error(source: &'s str, err: &TokenizeError) -> Self {
+        let bad = &source[err.at().min(source.len())..];
+        let literal_len = bad
+            .find(|ch: char| !ch.is_ascii_alphanumeric() && !matches!(ch, '.' | '_'))
+            .unwrap_or(bad.len());
+        let (len, help) = match err.kind() {
+            TokenizeErrorKind::InvalidSymbol if bad.starts_with('=') => (1, "use `==` to compare"),
+            TokenizeErrorKind::InvalidSymbol => (
+                bad.chars().next().map_or(0, char::len_utf8),
+                "expressions may only contain numbers, names, operators, parens and commas",
+            ),
+            TokenizeErrorKind::NumberTooLarge => (
+                literal_len,
+                if cfg!(feature = "bigint") {
+                    "use a float literal instead"
+                } else {
+                    "use a float literal or build with the `bigint` feature"
+                },
+            ),
+            TokenizeErrorKind::InvalidDigit => (
+                bad.chars().next().map_or(0, char::len_utf8),
+                "`0b` numbers take digits 0 and 1, `0o` ones 0 to 7, `0x` ones 0 to 9 and A to F",
+            ),
+            TokenizeErrorKind::MalformedNumber => (
+                literal_len,
+                "write numbers like `1_000`, `0xFF`, `0o17` or `0b1010`, with `_` only between digits",
+            ),
+        };
+        Self {
+            source,
+            message: err.kind().to_string(),
+            span: Some(Span::new(err.at(), err.at() + len)),
+            help: Some(help),
+        }
+    }
+
+    pub fn parse_error(source: &'s str, err: &ParseError) -> Self {
+        let text = |span: &Span| source.get(span.start..span.end).unwrap_or("");
+        let (message, help) = match err {
+            ParseError::Tokenization(tok_err) => return Self::tokenize_error(source, tok_err),
+            ParseError::UnmatchedParens(span) if text(span).ends_with('(') => {
+                ("this paren is never closed", Some("add a matching `)`"))
+            }
+            ParseError::UnmatchedParens(_) => ("this paren has no matching `(`", Some("remove it or add a `(` before")),
+            ParseError::NotEnoughArgs(span) if matches!(text(span), ")" | "," | ":") => {
+                ("expected an argument before this", None)
+            }
+            ParseError::NotEnoughArgs(_) => ("operator is missing an argument", None),
+            ParseError::NotEnoughOps(span) => {
+                let numbers = text(span).starts_with(|ch: char| ch.is_ascii_digit() || ch == '.')
+                    && source[..span.start].trim_end().ends_with(|ch: char| ch.is_ascii_digit());
+                let help = if numbers {
+                    "did you forget an operator between these numbers?"
+                } else {
+                    "did you forget an operator before this?"
+                };
+                ("got arguments without operator", Some(help))
+            }
+            ParseError::UnexpectedComma(_) => (
+                "got comma outside of function call",
+                Some("commas only separate function arguments, like in `max(a, b)`"),
+            ),
+            ParseError::UnmatchedTernary(span) if text(span) == "?" => {
+                ("this `?` has no matching `:`", Some("add `: value` for when the condition is false"))
+            }
+            ParseError::UnmatchedTernary(_) => {
+                ("this `:` has no matching `?`", Some("conditionals are written like `cond ? a : b`"))
+            }
+            ParseError::Empty => ("empty expression", None),
+        };
+        Self { source, message: message.to_owned(), span: err.span(), help }
+    }
+
+    pub fn render(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Display for Diagnostic<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("error: {}\n", self.message))?;
+        let gutter = match self.span {
+            Some(span) => {
+                let pos = Position::locate(self.source, span.start);
+                let line = self.source.lines().nth(pos.line - 1).unwrap_or("");
+                let gutter = " ".repeat(pos.line.to_string().len());
+                // span may go past the end of the line, or be empty at the end of input
+                let width = self.source[span.start.min(self.source.len())..]
+                    .get(..span.len())
+                    .map_or(1, |text| text.lines().next().unwrap_or("").chars().count())
+                    .max(1);
+                f.write_fmt(format_args!("{gutter}--> {pos}\n"))?;
+                f.write_fmt(format_args!("{gutter} |\n"))?;
+                f.write_fmt(format_args!("{} | {line}\n", pos.line))?;
+                f.write_fmt(format_args!(
+                    "{gutter} | {}{}\n",
+                    " ".repeat(pos.column - 1),
+                    "^".repeat(width)
+                ))?;
+                gutter
+            }
+            None => String::new(),
+        };
+        if let Some(help) = self.help {
+            f.write_fmt(format_args!("{gutter} = help: {help}\n"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders a parse error of `source`, see [`Diagnostic`]
+pub fn render_parse_error(source: &str, err: &ParseError) -> String {
+    Diagnostic::parse_error(source, err).render()
+}
+
+/// Renders a tokenize error of `source`, see [`Diagnostic`]
+pub fn render_tokenize_error(source: &str, err: &TokenizeError) -> String {
+    Diagnostic::tokenize_error(source, err).render()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse;
+
+    use super::render_parse_error;
+
+    fn render(source: &str) -> String {
+        let err = parse(source).collect::<Result<Vec<_>, _>>().unwrap_err();
+        render_parse_error(source, &err)
+    }
+
+    #[test]
+    fn missing_operator() {
+        assert_eq!(
+            render("12 + 1 2"),
+            "error: got arguments without operator\n \
+             --> 1:8\n  \
+             |\n\
+             1 | 12 + 1 2\n  \
+             |        ^\n  \
+             = help: did you forget an operator between these numbers?\n"
+        );
+        assert!(render("1 x").contains("did you forget an operator before this?"));
+    }
+
+    #[test]
+    fn parens() {
+        assert_eq!(
+            render("max(1, (2 + 3)"),
+            "error: this paren is never closed\n \
+             --> 1:1\n  \
+             |\n\
+             1 | max(1, (2 + 3)\n  \
+             | ^^^^\n  \
+             = help: add a matching `)`\n"
+        );
+        assert!(render("1 + 2)").contains("1 | 1 + 2)\n  |      ^\n"));
+    }
+
+    #[test]
+    fn tokenize_errors() {
+        assert_eq!(
+            render("1 +\n  2 € 3"),
+            "error: invalid symbol\n \
+             --> 2:5\n  \
+             |\n\
+             2 |   2 € 3\n  \
+             |     ^\n  \
+             = help: expressions may only contain numbers, names, operators, parens and commas\n"
+        );
+    }
+
+    #[test]
+    fn other_errors() {
+        assert!(render("1 +* 2").contains("error: operator is missing an argument\n"));
+        assert!(render("max(1,)").contains("error: expected an argument before this\n"));
+        assert!(render("1, 2").contains("= help: commas only separate function arguments"));
+        assert!(render("x = 2").contains("= help: use `==` to compare"));
+        assert!(render("0b1021").contains("error: invalid digit for the base of the number\n"));
+        assert!(render("0x + 1").contains("1 | 0x + 1\n  | ^^\n"));
+        assert!(render("a > 1 ? b").contains("error: this `?` has no matching `:`\n"));
+        assert!(render("a ? : b").contains("error: expected an argument before this\n"));
+    }
+}
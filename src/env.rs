@@ -0,0 +1,20 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::value::Value;
+
+/// Variables visible to [`crate::Calculator`]
+pub trait Environment {
+    fn lookup(&self, name: &str) -> Option<Value>;
+}
+
+impl Environment for HashMap<String, Value> {
+    fn lookup(&self, name: &str) -> Option<Value> {
+        self.get(name).cloned()
+    }
+}
+
+impl Environment for BTreeMap<String, Value> {
+    fn lookup(&self, name: &str) -> Option<Value> {
+        self.get(name).cloned()
+    }
+}
@@ -0,0 +1,101 @@
+use std::{collections::HashMap, fmt::Debug, sync::OnceLock};
+
+use crate::{
+    tokens::{Int, UnaryOperation},
+    value::Value,
+    EvalError, OverflowPolicy,
+};
+
+/// Number of arguments a function accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exact(usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match self {
+            Arity::Exact(arity) => count == *arity,
+            Arity::AtLeast(arity) => count >= *arity,
+        }
+    }
+}
+
+pub(crate) type Function = Box<dyn Fn(&[Value]) -> Result<Value, EvalError> + Send + Sync>;
+
+/// Registry of functions callable from expressions, see [`crate::Calculator::with_functions`]
+#[derive(Default)]
+pub struct Functions {
+    funcs: HashMap<String, (Arity, Function)>,
+}
+
+impl Functions {
+    /// Registry without any functions
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry with `max`, `min`, `abs` and `sqrt`.
+    /// Functions don't see the [`OverflowPolicy`] of the calculator, so `abs` always checks:
+    /// `abs(i64::MIN)` fails with [`EvalError::Overflow`] even when wrapping
+    pub fn builtin() -> Self {
+        let mut funcs = Self::new();
+        funcs.register("max", Arity::AtLeast(1), |args| Ok(pick(numbers(args)?, |lhs, rhs| lhs > rhs)));
+        funcs.register("min", Arity::AtLeast(1), |args| Ok(pick(numbers(args)?, |lhs, rhs| lhs < rhs)));
+        funcs.register("abs", Arity::Exact(1), |args| match &numbers(args)?[0] {
+            arg if *arg < Value::Int(Int::from(0)) => {
+                // under any policy, nothing else makes sense for the absolute value
+                Value::apply_unary(UnaryOperation::Neg, arg.clone(), OverflowPolicy::Checked)
+            }
+            arg => Ok(arg.clone()),
+        });
+        funcs.register("sqrt", Arity::Exact(1), |args| Ok(Value::Float(numbers(args)?[0].as_f64().sqrt())));
+        funcs
+    }
+
+    /// Adds a function, replacing the one with the same name if any.
+    /// `fun` is only called with argument count accepted by `arity`
+    pub fn register<F>(&mut self, name: impl Into<String>, arity: Arity, fun: F)
+    where
+        F: Fn(&[Value]) -> Result<Value, EvalError> + Send + Sync + 'static,
+    {
+        self.funcs.insert(name.into(), (arity, Box::new(fun)));
+    }
+
+    pub fn call(&self, name: &str, args: &[Value]) -> Result<Value, EvalError> {
+        match self.funcs.get(name) {
+            Some((arity, fun)) if arity.accepts(args.len()) => fun(args),
+            Some(_) => Err(EvalError::WrongArgCount { name: name.to_owned(), got: args.len() }),
+            None => Err(EvalError::UnknownFunction(name.to_owned())),
+        }
+    }
+}
+
+/// Builtins only take numbers
+fn numbers(args: &[Value]) -> Result<&[Value], EvalError> {
+    if args.iter().any(|arg| matches!(arg, Value::Bool(_))) {
+        return Err(EvalError::NotANumber);
+    }
+    Ok(args)
+}
+
+impl Debug for Functions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.funcs.iter().map(|(name, (arity, _))| (name, arity)))
+            .finish()
+    }
+}
+
+/// Shared [`Functions::builtin`] registry
+pub(crate) fn builtins() -> &'static Functions {
+    static BUILTINS: OnceLock<Functions> = OnceLock::new();
+    BUILTINS.get_or_init(Functions::builtin)
+}
+
+fn pick(args: &[Value], better: fn(&Value, &Value) -> bool) -> Value {
+    args.iter()
+        .fold(&args[0], |best, arg| if better(arg, best) { arg } else { best })
+        .clone()
+}
@@ -0,0 +1,180 @@
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display},
+    sync::{Arc, OnceLock},
+};
+
+use crate::{
+    functions::Function,
+    tokenizer,
+    tokens::{Assoc, CustomOp, Operation, Token, UnaryOperation},
+    value::Value,
+    EvalError,
+};
+
+/// Where the operands of a registered operator go
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fixity {
+    /// Before its only operand, like `-x`
+    Prefix,
+    /// Between its two operands
+    Infix(Assoc),
+}
+
+/// Why [`Grammar::register`] refused an operator
+#[derive(Debug, PartialEq, Eq)]
+pub enum GrammarError {
+    /// Neither a word like `mod` nor punctuation like `<=>`
+    InvalidSymbol(String),
+    /// Precedence that isn't positive
+    InvalidPrecedence(i32),
+}
+
+impl Display for GrammarError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GrammarError::InvalidSymbol(symbol) => {
+                f.write_fmt(format_args!("operator symbol must be a word or punctuation, got {symbol:?}"))
+            }
+            GrammarError::InvalidPrecedence(prio) => {
+                f.write_fmt(format_args!("operator precedence must be positive, got {prio}"))
+            }
+        }
+    }
+}
+
+impl std::error::Error for GrammarError {}
+
+/// Operators on top of the builtin ones, see [`crate::tokenizer::Tokenizer::with_grammar`],
+/// [`crate::parser::Parser::with_grammar`] and [`crate::Calculator::with_grammar`]
+#[derive(Default)]
+pub struct Grammar {
+    prefix: HashMap<Arc<str>, (CustomOp, Function)>,
+    infix: HashMap<Arc<str>, (CustomOp, Function)>,
+}
+
+impl Grammar {
+    /// Grammar with the builtin operators only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an operator, replacing the one with the same symbol and fixity if any.
+    /// A symbol may be registered both as prefix and infix one, like `-`, and takes
+    /// over the builtin operator it matches.
+    ///
+    /// `prio` is on the scale of the builtin operators, higher binds tighter:
+    /// 1 for `||`, 6 for `==`, 9 for `+`, 10 for `*`, 11 for prefix ones and 12 for `^`.
+    /// `fun` gets one operand for a prefix operator and two for an infix one.
+    ///
+    /// Fails if `symbol` is neither a word like `mod` nor punctuation like `<=>`,
+    /// or if `prio` isn't positive
+    pub fn register<F>(
+        &mut self,
+        symbol: impl Into<String>,
+        fixity: Fixity,
+        prio: i32,
+        fun: F,
+    ) -> Result<(), GrammarError>
+    where
+        F: Fn(&[Value]) -> Result<Value, EvalError> + Send + Sync + 'static,
+    {
+        let symbol = symbol.into();
+        if !is_symbol(&symbol) {
+            return Err(GrammarError::InvalidSymbol(symbol));
+        }
+        if prio <= 0 {
+            return Err(GrammarError::InvalidPrecedence(prio));
+        }
+        let symbol: Arc<str> = symbol.into();
+        let (table, assoc) = match fixity {
+            // nothing is on the left to group with
+            Fixity::Prefix => (&mut self.prefix, Assoc::Right),
+            Fixity::Infix(assoc) => (&mut self.infix, assoc),
+        };
+        let op = CustomOp { symbol: symbol.clone(), prio, assoc };
+        table.insert(symbol, (op, Box::new(fun)));
+        Ok(())
+    }
+
+    /// Evaluates a registered operator, prefix one for a single operand, infix one for two
+    pub fn call(&self, symbol: &str, args: &[Value]) -> Result<Value, EvalError> {
+        let registered = match args.len() {
+            1 => self.prefix.get(symbol),
+            2 => self.infix.get(symbol),
+            _ => None,
+        };
+        match registered {
+            Some((_, fun)) => fun(args),
+            None => Err(EvalError::UnknownOperator(symbol.to_owned())),
+        }
+    }
+
+    /// Token for a registered symbol, the infix form if there is one, builtin or registered,
+    /// the parser switches to the prefix one when there is no left operand
+    pub(crate) fn token(&self, symbol: &str) -> Option<Token> {
+        match (self.infix.get(symbol), self.prefix.get(symbol)) {
+            (Some((op, _)), _) => Some(Token::Oper(Operation::Custom(op.clone()))),
+            (None, Some((op, _))) => Some(match tokenizer::builtin_oper(symbol) {
+                Some(builtin) => Token::Oper(builtin),
+                None => Token::UnaryOper(UnaryOperation::Custom(op.clone())),
+            }),
+            (None, None) => None,
+        }
+    }
+
+    /// Longest registered symbol `part` starts with
+    pub(crate) fn longest_symbol(&self, part: &str) -> Option<&str> {
+        self.infix
+            .keys()
+            .chain(self.prefix.keys())
+            .map(|symbol| &**symbol)
+            .filter(|symbol| part.starts_with(symbol))
+            .max_by_key(|symbol| symbol.len())
+    }
+
+    /// Form of `op` that takes no left operand, if it has one, registered or builtin:
+    /// registering only infix `-` keeps the builtin negation and vice versa
+    pub(crate) fn prefix_form(&self, op: &Operation) -> Option<UnaryOperation> {
+        let registered = match op {
+            Operation::Custom(op) => self.prefix.get(&op.symbol),
+            _ => self
+                .prefix
+                .iter()
+                .find(|(symbol, _)| tokenizer::builtin_oper(symbol).as_ref() == Some(op))
+                .map(|(_, registered)| registered),
+        };
+        match (registered, op) {
+            (Some((op, _)), _) => Some(UnaryOperation::Custom(op.clone())),
+            (None, Operation::Custom(op)) => UnaryOperation::from_binary(&tokenizer::builtin_oper(&op.symbol)?),
+            (None, _) => UnaryOperation::from_binary(op),
+        }
+    }
+}
+
+/// Anything else would be split into several tokens
+fn is_symbol(symbol: &str) -> bool {
+    let mut chars = symbol.chars();
+    match chars.next() {
+        Some(ch) if tokenizer::is_ident_start(ch) => chars.all(tokenizer::is_ident_continue),
+        Some(_) => symbol
+            .chars()
+            .all(|ch| !tokenizer::is_ident_continue(ch) && !ch.is_whitespace() && !matches!(ch, '(' | ')' | ',')),
+        None => false,
+    }
+}
+
+impl Debug for Grammar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Grammar")
+            .field("prefix", &self.prefix.values().map(|(op, _)| op).collect::<Vec<_>>())
+            .field("infix", &self.infix.values().map(|(op, _)| op).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Shared [`Grammar::new`], the one used unless another is given
+pub(crate) fn builtin() -> &'static Grammar {
+    static BUILTIN: OnceLock<Grammar> = OnceLock::new();
+    BUILTIN.get_or_init(Grammar::new)
+}
@@ -1,68 +1,421 @@
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
 
-use tokens::{Number, Operation, Paren, Token};
+use env::Environment;
+use functions::Functions;
+use grammar::Grammar;
+use tokens::{Int, Logic, Number, Operation, Paren, Token, UnaryOperation};
+use value::Value;
 
+pub mod ast;
+pub mod diagnostics;
+pub mod env;
+pub mod functions;
+pub mod grammar;
+pub mod notation;
 pub mod parser;
+pub mod recovery;
+pub mod rpn;
 pub mod tokenizer;
 pub mod tokens;
+pub mod value;
 
 pub trait TokenVisitor {
     fn visit_paren(&mut self, paren: Paren);
     fn visit_op(&mut self, op: Operation);
+    fn visit_unary(&mut self, op: UnaryOperation);
     fn visit_num(&mut self, num: Number);
+    fn visit_ident(&mut self, name: String);
+    fn visit_comma(&mut self);
+    fn visit_call(&mut self, name: String, arity: usize);
+    fn visit_question(&mut self);
+    fn visit_colon(&mut self);
 
     fn visit_token(&mut self, tok: Token) {
         match tok {
             Token::Number(num) => self.visit_num(num),
+            Token::Ident(name) => self.visit_ident(name),
             Token::Paren(paren) => self.visit_paren(paren),
             Token::Oper(op) => self.visit_op(op),
+            Token::UnaryOper(op) => self.visit_unary(op),
+            Token::Comma => self.visit_comma(),
+            Token::Call { name, arity } => self.visit_call(name, arity),
+            Token::Question => self.visit_question(),
+            Token::Colon => self.visit_colon(),
         }
     }
 }
 
-#[derive(Debug)]
-struct Calculator {
-    stack: Vec<i64>,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    StackUnderflow,
+    LeftoverOperands,
+    UnexpectedParen,
+    UnexpectedComma,
+    UnexpectedColon,
+    DivisionByZero,
+    Overflow,
+    /// Integer power with a negative exponent
+    NegativeExponent,
+    /// Variable is missing from the [`Environment`]
+    UnknownVariable(String),
+    /// Function is missing from the [`Functions`] registry
+    UnknownFunction(String),
+    /// Custom operator is missing from the [`Grammar`]
+    UnknownOperator(String),
+    WrongArgCount { name: String, got: usize },
+    /// Integer result was requested, but evaluation produced a float
+    NotAnInteger,
+    /// Arithmetic or ordering on a boolean
+    NotANumber,
+    /// Logical operation on a number
+    NotABool,
+    /// Bitwise operation on a float
+    BitwiseOnFloat,
+    /// Negative shift, or one by the width of the integer or more
+    ShiftOutOfRange,
+    /// Comparison or bitwise operation given to the arithmetic [`Operation::apply`]
+    NotArithmetic,
 }
 
-impl Calculator {
-    fn new() -> Self {
-        Self { stack: vec![] }
+impl Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::StackUnderflow => f.write_str("insufficient arguments for operation"),
+            EvalError::LeftoverOperands => f.write_str("not all arguments have corresponding operators"),
+            EvalError::UnexpectedParen => f.write_str("got parens, use parser first"),
+            EvalError::UnexpectedComma => f.write_str("got comma, use parser first"),
+            EvalError::UnexpectedColon => f.write_str("got colon, use parser first"),
+            EvalError::DivisionByZero => f.write_str("division by zero"),
+            EvalError::Overflow => f.write_str("integer overflow"),
+            EvalError::NegativeExponent => f.write_str("negative exponent in integer power"),
+            EvalError::UnknownVariable(name) => f.write_fmt(format_args!("unknown variable \"{name}\"")),
+            EvalError::UnknownFunction(name) => f.write_fmt(format_args!("unknown function \"{name}\"")),
+            EvalError::UnknownOperator(symbol) => f.write_fmt(format_args!("unknown operator \"{symbol}\"")),
+            EvalError::WrongArgCount { name, got } => {
+                f.write_fmt(format_args!("function \"{name}\" can't take {got} arguments"))
+            }
+            EvalError::NotAnInteger => f.write_str("result is not an integer"),
+            EvalError::NotANumber => f.write_str("expected a number, got a boolean"),
+            EvalError::NotABool => f.write_str("expected a boolean, got a number"),
+            EvalError::BitwiseOnFloat => f.write_str("bitwise operations take integers, got a float"),
+            EvalError::ShiftOutOfRange => f.write_str("shift amount out of range"),
+            EvalError::NotArithmetic => f.write_str("expected an arithmetic operation"),
+        }
     }
+}
+
+impl std::error::Error for EvalError {}
+
+/// What integer evaluation does when a result doesn't fit into `i64`.
+/// Operations on big integers only overflow when a power or a shift gets too large
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OverflowPolicy {
+    /// Fail with [`EvalError::Overflow`]
+    #[default]
+    Checked,
+    /// Wrap around at the boundary of the type
+    Wrapping,
+    /// Clamp to `i64::MIN` or `i64::MAX`
+    Saturating,
+    /// Go on with [`Value::Big`]
+    #[cfg(feature = "bigint")]
+    Promote,
+}
+
+#[derive(Default)]
+pub struct Calculator<'e> {
+    stack: Vec<Value>,
+    overflow: OverflowPolicy,
+    /// Without environment any variable is unknown
+    env: Option<&'e dyn Environment>,
+    /// [`Functions::builtin`] if not set
+    functions: Option<&'e Functions>,
+    /// Evaluates custom operators, without it any of them is unknown
+    grammar: Option<&'e Grammar>,
+    /// First error met, the rest of the input is ignored once it is set
+    error: Option<EvalError>,
+}
 
-    fn calculate<I: Iterator<Item = Token>>(&mut self, mut iter: I) -> i64 {
-        while let Some(tok) = iter.next() {
-            self.visit_token(tok);
+impl<'e> Calculator<'e> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_overflow(self, overflow: OverflowPolicy) -> Self {
+        Self { overflow, ..self }
+    }
+
+    pub fn with_env(self, env: &'e dyn Environment) -> Self {
+        Self { env: Some(env), ..self }
+    }
+
+    pub fn with_functions(self, functions: &'e Functions) -> Self {
+        Self { functions: Some(functions), ..self }
+    }
+
+    pub fn with_grammar(self, grammar: &'e Grammar) -> Self {
+        Self { grammar: Some(grammar), ..self }
+    }
+
+    /// Evaluates tokens in postfix order, as produced by [`parser::Parser`].
+    /// Fails with [`EvalError::NotAnInteger`] if the result is a float or a boolean,
+    /// and with [`EvalError::Overflow`] if it is a big integer
+    pub fn try_calculate<I: Iterator<Item = Token>>(&mut self, iter: I) -> Result<Int, EvalError> {
+        match self.try_evaluate(iter)? {
+            Value::Int(num) => Ok(num),
+            Value::Float(_) | Value::Bool(_) => Err(EvalError::NotAnInteger),
+            #[cfg(feature = "bigint")]
+            Value::Big(_) => Err(EvalError::Overflow),
         }
-        if self.stack.is_empty() {
-            0
-        } else if self.stack.len() > 1 {
-            panic!("Not all arguments have corresponding operators")
-        } else {
-            self.stack.pop().unwrap()
+    }
+
+    /// Same as [`Calculator::try_calculate`], but takes integers of any size
+    #[cfg(feature = "bigint")]
+    pub fn try_calculate_big<I: Iterator<Item = Token>>(&mut self, iter: I) -> Result<tokens::BigInt, EvalError> {
+        match self.try_evaluate(iter)? {
+            Value::Int(num) => Ok(num.into()),
+            Value::Big(num) => Ok(num),
+            Value::Float(_) | Value::Bool(_) => Err(EvalError::NotAnInteger),
+        }
+    }
+
+    /// Same as [`Calculator::try_calculate`], but allows float and boolean results.
+    /// Right operand of `&&` and `||` is skipped when the left one decides the result,
+    /// so is the branch of a conditional that is not taken
+    pub fn try_evaluate<I: Iterator<Item = Token>>(&mut self, iter: I) -> Result<Value, EvalError> {
+        self.stack.clear();
+        self.error = None;
+        let tokens: Vec<_> = iter.collect();
+        let jumps = lazy_operands(&tokens);
+        let mut idx = 0;
+        // the second branch was jumped to, not reached after the first one
+        let mut to_otherwise = false;
+        while let Some(tok) = tokens.get(idx) {
+            let jump = if std::mem::take(&mut to_otherwise) { None } else { jumps[idx] };
+            match jump {
+                Some(Jump::ShortCircuit(logic, past_op)) => match self.stack.last() {
+                    Some(Value::Bool(lhs)) if logic.short_circuit(*lhs).is_some() => {
+                        // the left operand is the result
+                        idx = past_op;
+                        continue;
+                    }
+                    Some(Value::Bool(_)) => (),
+                    Some(_) => return Err(EvalError::NotABool),
+                    None => return Err(EvalError::StackUnderflow),
+                },
+                Some(Jump::Branch(otherwise)) => match self.stack.last() {
+                    Some(Value::Bool(true)) => (),
+                    Some(Value::Bool(false)) => {
+                        self.stack.push(SKIPPED);
+                        idx = otherwise;
+                        to_otherwise = true;
+                        continue;
+                    }
+                    Some(_) => return Err(EvalError::NotABool),
+                    None => return Err(EvalError::StackUnderflow),
+                },
+                Some(Jump::Merge(cond)) => {
+                    self.stack.push(SKIPPED);
+                    idx = cond;
+                    continue;
+                }
+                None => (),
+            }
+            self.visit_token(tok.clone());
+            if let Some(err) = self.error.take() {
+                return Err(err);
+            }
+            idx += 1;
+        }
+        match self.stack.len() {
+            0 => Ok(Value::Int(Int::from(0))),
+            1 => Ok(self.stack.pop().unwrap()),
+            _ => Err(EvalError::LeftoverOperands),
+        }
+    }
+
+    fn grammar(&self) -> &'e Grammar {
+        match self.grammar {
+            Some(grammar) => grammar,
+            None => grammar::builtin(),
+        }
+    }
+}
+
+/// Function that is evaluated as `cond ? a : b`, skipping the branch not taken
+const CONDITIONAL: &str = "if";
+
+/// Stands in for the branch of a conditional that is not evaluated, so that
+/// the conditional finds all three operands on the stack
+const SKIPPED: Value = Value::Bool(false);
+
+/// Where evaluation goes instead of the operand starting at the token
+#[derive(Debug, Clone, Copy)]
+enum Jump {
+    /// Right operand of `&&` and `||`, to right after the operator if it is not needed
+    ShortCircuit(Logic, usize),
+    /// First branch of a conditional, to the second one if the condition is false
+    Branch(usize),
+    /// Second branch of a conditional reached after the first one, to the conditional itself
+    Merge(usize),
+}
+
+/// Jumps for the operands that may be skipped, by the index of their first token
+fn lazy_operands(tokens: &[Token]) -> Vec<Option<Jump>> {
+    let mut jumps = vec![None; tokens.len()];
+    // where each operand on the stack starts
+    let mut starts = vec![];
+    for (idx, tok) in tokens.iter().enumerate() {
+        let arity = match tok {
+            Token::Number(_) | Token::Ident(_) | Token::Paren(_) | Token::Comma | Token::Colon => 0,
+            Token::UnaryOper(_) => 1,
+            Token::Oper(_) => 2,
+            Token::Question => 3,
+            Token::Call { arity, .. } => *arity,
+        };
+        let Some(first) = starts.len().checked_sub(arity) else {
+            // malformed, evaluation will tell
+            break;
+        };
+        let args = starts.split_off(first);
+        match (tok, args.as_slice()) {
+            (Token::Oper(Operation::Logic(logic)), [_, rhs]) => jumps[*rhs] = Some(Jump::ShortCircuit(*logic, idx + 1)),
+            (Token::Question, [_, then, otherwise]) => {
+                jumps[*then] = Some(Jump::Branch(*otherwise));
+                jumps[*otherwise] = Some(Jump::Merge(idx));
+            }
+            (Token::Call { name, .. }, [_, then, otherwise]) if name == CONDITIONAL => {
+                jumps[*then] = Some(Jump::Branch(*otherwise));
+                jumps[*otherwise] = Some(Jump::Merge(idx));
+            }
+            _ => (),
         }
+        starts.push(args.first().copied().unwrap_or(idx));
     }
+    jumps
 }
 
-pub fn calculate<I: Iterator<Item = Token>>(iter: I) -> i64 {
-    Calculator::new().calculate(iter)
+impl Debug for Calculator<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Calculator")
+            .field("stack", &self.stack)
+            .field("overflow", &self.overflow)
+            .field("env", &self.env.map(|_| ".."))
+            .field("functions", &self.functions)
+            .field("grammar", &self.grammar)
+            .field("error", &self.error)
+            .finish()
+    }
 }
 
-impl TokenVisitor for Calculator {
+/// [`Calculator::try_calculate`] with default settings
+pub fn try_calculate<I: Iterator<Item = Token>>(iter: I) -> Result<Int, EvalError> {
+    Calculator::new().try_calculate(iter)
+}
+
+/// [`Calculator::try_calculate_big`] with default settings
+#[cfg(feature = "bigint")]
+pub fn try_calculate_big<I: Iterator<Item = Token>>(iter: I) -> Result<tokens::BigInt, EvalError> {
+    Calculator::new().try_calculate_big(iter)
+}
+
+/// [`Calculator::try_evaluate`] with default settings
+pub fn try_evaluate<I: Iterator<Item = Token>>(iter: I) -> Result<Value, EvalError> {
+    Calculator::new().try_evaluate(iter)
+}
+
+/// Panicking version of [`try_calculate`]
+pub fn calculate<I: Iterator<Item = Token>>(iter: I) -> Int {
+    try_calculate(iter).unwrap_or_else(|err| panic!("Couldn't calculate: {err}"))
+}
+
+impl TokenVisitor for Calculator<'_> {
     fn visit_paren(&mut self, _par: Paren) {
-        panic!("Calculator should not face parens, use pareser first")
+        self.error = Some(EvalError::UnexpectedParen);
     }
 
     fn visit_op(&mut self, op: Operation) {
         if let (Some(rhs), Some(lhs)) = (self.stack.pop(), self.stack.pop()) {
-            self.stack.push(op.apply(lhs, rhs))
+            let res = match op {
+                Operation::Custom(op) => self.grammar().call(op.symbol(), &[lhs, rhs]),
+                _ => Value::apply(op, lhs, rhs, self.overflow),
+            };
+            match res {
+                Ok(res) => self.stack.push(res),
+                Err(err) => self.error = Some(err),
+            }
         } else {
-            panic!("Insufficient arguments for operation")
+            self.error = Some(EvalError::StackUnderflow);
+        }
+    }
+
+    fn visit_unary(&mut self, op: UnaryOperation) {
+        if let Some(arg) = self.stack.pop() {
+            let res = match op {
+                UnaryOperation::Custom(op) => self.grammar().call(op.symbol(), &[arg]),
+                _ => Value::apply_unary(op, arg, self.overflow),
+            };
+            match res {
+                Ok(res) => self.stack.push(res),
+                Err(err) => self.error = Some(err),
+            }
+        } else {
+            self.error = Some(EvalError::StackUnderflow);
+        }
+    }
+
+    fn visit_num(&mut self, num: Number) {
+        self.stack.push(Value::from(num));
+    }
+
+    fn visit_ident(&mut self, name: String) {
+        match self.env.and_then(|env| env.lookup(&name)) {
+            Some(val) => self.stack.push(val),
+            None => self.error = Some(EvalError::UnknownVariable(name)),
+        }
+    }
+
+    fn visit_comma(&mut self) {
+        self.error = Some(EvalError::UnexpectedComma);
+    }
+
+    fn visit_call(&mut self, name: String, arity: usize) {
+        match (name.as_str(), arity) {
+            (CONDITIONAL, 3) => return self.visit_question(),
+            (CONDITIONAL, got) => {
+                self.error = Some(EvalError::WrongArgCount { name, got });
+                return;
+            }
+            _ => (),
+        }
+        let Some(args_start) = self.stack.len().checked_sub(arity) else {
+            self.error = Some(EvalError::StackUnderflow);
+            return;
+        };
+        let args = self.stack.split_off(args_start);
+        let functions = match self.functions {
+            Some(functions) => functions,
+            None => functions::builtins(),
+        };
+        match functions.call(&name, &args) {
+            Ok(res) => self.stack.push(res),
+            Err(err) => self.error = Some(err),
+        }
+    }
+
+    fn visit_question(&mut self) {
+        match (self.stack.pop(), self.stack.pop(), self.stack.pop()) {
+            (Some(otherwise), Some(then), Some(Value::Bool(cond))) => {
+                self.stack.push(if cond { then } else { otherwise });
+            }
+            (Some(_), Some(_), Some(_)) => self.error = Some(EvalError::NotABool),
+            _ => self.error = Some(EvalError::StackUnderflow),
         }
     }
 
-    fn visit_num(&mut self, Number(num): Number) {
-        self.stack.push(num);
+    fn visit_colon(&mut self) {
+        self.error = Some(EvalError::UnexpectedColon);
     }
 }
 
@@ -77,7 +430,7 @@ impl Display for Printer<'_> {
             return Ok(());
         }
 
-        let (fst, rest) = (toks[0], &toks[1..]);
+        let (fst, rest) = (&toks[0], &toks[1..]);
         f.write_fmt(format_args!("{fst}"))?;
         for tok in rest {
             f.write_fmt(format_args!(" {tok}"))?;
@@ -86,17 +439,36 @@ impl Display for Printer<'_> {
     }
 }
 
+/// Integer literals in another base, e.g. `{:#x}` gives `0xff 0x10 +`
+impl Printer<'_> {
+    fn fmt_radix(&self, f: &mut std::fmt::Formatter<'_>, fmt_int: tokens::FmtInt) -> std::fmt::Result {
+        for (idx, tok) in self.0.iter().enumerate() {
+            if idx != 0 {
+                f.write_str(" ")?;
+            }
+            tok.fmt_radix(f, fmt_int)?;
+        }
+        Ok(())
+    }
+}
+
+tokens::radix_fmt!(Printer<'_>);
+
 #[cfg(test)]
 mod tests {
     use crate::{
-        calculate,
+        calculate, try_calculate, try_evaluate, EvalError,
         parser::{ParseError, Parser},
         tokenizer::{tokenize, TokenizeError},
-        tokens::{Number, Operation, Paren, Token},
+        tokens::{Comparison, Int, Logic, Number, Operation, Paren, Span, Token, UnaryOperation},
     };
 
     fn num(num: i64) -> Token {
-        Token::Number(Number(num))
+        Token::Number(Number::Int(num))
+    }
+
+    fn float(num: f64) -> Token {
+        Token::Number(Number::Float(num))
     }
 
     fn op(op: char) -> Token {
@@ -105,12 +477,35 @@ mod tests {
             '-' => Operation::Sub,
             '*' => Operation::Mul,
             '/' => Operation::Div,
+            '^' => Operation::Pow,
+            '%' => Operation::Rem,
+            '\\' => Operation::FloorDiv,
+            '=' => Operation::Cmp(Comparison::Eq),
+            '≠' => Operation::Cmp(Comparison::Ne),
+            '<' => Operation::Cmp(Comparison::Lt),
+            '≤' => Operation::Cmp(Comparison::Le),
+            '>' => Operation::Cmp(Comparison::Gt),
+            '≥' => Operation::Cmp(Comparison::Ge),
+            '&' => Operation::Logic(Logic::And),
+            '|' => Operation::Logic(Logic::Or),
             _ => panic!("Unsupported oper shortcut"),
         };
 
         Token::Oper(op)
     }
 
+    fn unary(op: char) -> Token {
+        let op = match op {
+            '+' => UnaryOperation::Plus,
+            '-' => UnaryOperation::Neg,
+            '!' => UnaryOperation::Not,
+            '~' => UnaryOperation::BitNot,
+            _ => panic!("Unsupported unary oper shortcut"),
+        };
+
+        Token::UnaryOper(op)
+    }
+
     const LPAR: Token = Token::Paren(Paren::Left);
     const RPAR: Token = Token::Paren(Paren::Right);
 
@@ -214,40 +609,651 @@ mod tests {
         )
     }
 
+    #[test]
+    fn unary_ops() {
+        test("-3", vec![op('-'), num(3)], vec![num(3), unary('-')], -3);
+        test(
+            "2 * -1",
+            vec![num(2), op('*'), op('-'), num(1)],
+            vec![num(2), num(1), unary('-'), op('*')],
+            -2,
+        );
+        test(
+            "-(1 + 2)",
+            vec![op('-'), LPAR, num(1), op('+'), num(2), RPAR],
+            vec![num(1), num(2), op('+'), unary('-')],
+            -3,
+        );
+        test(
+            "1 +- 2",
+            vec![num(1), op('+'), op('-'), num(2)],
+            vec![num(1), num(2), unary('-'), op('+')],
+            -1,
+        );
+        test(
+            "-3 * 2 - +-4",
+            vec![op('-'), num(3), op('*'), num(2), op('-'), op('+'), op('-'), num(4)],
+            vec![num(3), unary('-'), num(2), op('*'), num(4), unary('-'), unary('+'), op('-')],
+            -2,
+        );
+    }
+
+    #[test]
+    fn power() {
+        test(
+            "2 ^ 3 ^ 2",
+            vec![num(2), op('^'), num(3), op('^'), num(2)],
+            vec![num(2), num(3), num(2), op('^'), op('^')],
+            512,
+        );
+        test(
+            "2 ** 3 * 2",
+            vec![num(2), op('^'), num(3), op('*'), num(2)],
+            vec![num(2), num(3), op('^'), num(2), op('*')],
+            16,
+        );
+        test(
+            "-2^2",
+            vec![op('-'), num(2), op('^'), num(2)],
+            vec![num(2), num(2), op('^'), unary('-')],
+            -4,
+        );
+        test(
+            "8 / 4 / 2",
+            vec![num(8), op('/'), num(4), op('/'), num(2)],
+            vec![num(8), num(4), op('/'), num(2), op('/')],
+            1,
+        );
+        assert_eq!(
+            try_calculate([num(2), num(1), unary('-'), op('^')].into_iter()),
+            Err(EvalError::NegativeExponent)
+        );
+        assert_eq!(
+            try_evaluate([float(2.0), num(1), unary('-'), op('^')].into_iter()),
+            Ok(crate::value::Value::Float(0.5))
+        );
+    }
+
+    #[test]
+    fn floor_division() {
+        use crate::value::Value;
+
+        test(
+            "7 // 2 * 2 + 7 % 2",
+            vec![num(7), op('\\'), num(2), op('*'), num(2), op('+'), num(7), op('%'), num(2)],
+            vec![num(7), num(2), op('\\'), num(2), op('*'), num(7), num(2), op('%'), op('+')],
+            7,
+        );
+        assert_eq!(eval("-7 // 2"), Ok(Value::Int(-4)));
+        assert_eq!(eval("-7 % 3"), Ok(Value::Int(2)));
+        assert_eq!(eval("7 % -3"), Ok(Value::Int(-2)));
+        assert_eq!(eval("-7 % -3"), Ok(Value::Int(-1)));
+        assert_eq!(eval("-7.5 // 2"), Ok(Value::Float(-4.0)));
+        assert_eq!(eval("-7.5 % 2"), Ok(Value::Float(0.5)));
+        assert_eq!(eval("1 % 0"), Err(EvalError::DivisionByZero));
+        assert_eq!(eval("1 // 0"), Err(EvalError::DivisionByZero));
+    }
+
+    #[test]
+    fn variables() {
+        use std::collections::HashMap;
+
+        use crate::{value::Value, Calculator};
+
+        let env: HashMap<_, _> = [
+            ("price".to_owned(), Value::Float(2.5)),
+            ("qty".to_owned(), Value::Int(Int::from(4))),
+        ]
+        .into_iter()
+        .collect();
+        let eval = |input: &str| Calculator::new().with_env(&env).try_evaluate(rpn(input).into_iter());
+
+        assert_eq!(eval("price * qty"), Ok(Value::Float(10.0)));
+        assert_eq!(eval("qty * (qty - 1)"), Ok(Value::Int(Int::from(12))));
+        assert_eq!(eval("price * amount"), Err(EvalError::UnknownVariable("amount".to_owned())));
+        assert_eq!(
+            try_evaluate(Parser::new(tokenize("x")).map(Result::unwrap)),
+            Err(EvalError::UnknownVariable("x".to_owned()))
+        );
+    }
+
+    fn call(name: &str, arity: usize) -> Token {
+        Token::Call { name: name.to_owned(), arity }
+    }
+
+    #[test]
+    fn functions() {
+        test(
+            "max(1, 2 * 3) - abs(-4)",
+            vec![
+                Token::Ident("max".to_owned()),
+                LPAR,
+                num(1),
+                Token::Comma,
+                num(2),
+                op('*'),
+                num(3),
+                RPAR,
+                op('-'),
+                Token::Ident("abs".to_owned()),
+                LPAR,
+                op('-'),
+                num(4),
+                RPAR,
+            ],
+            vec![num(1), num(2), num(3), op('*'), call("max", 2), num(4), unary('-'), call("abs", 1), op('-')],
+            2,
+        );
+        test(
+            "min(3, max(1, 2), (4))",
+            vec![
+                Token::Ident("min".to_owned()),
+                LPAR,
+                num(3),
+                Token::Comma,
+                Token::Ident("max".to_owned()),
+                LPAR,
+                num(1),
+                Token::Comma,
+                num(2),
+                RPAR,
+                Token::Comma,
+                LPAR,
+                num(4),
+                RPAR,
+                RPAR,
+            ],
+            vec![num(3), num(1), num(2), call("max", 2), num(4), call("min", 3)],
+            2,
+        );
+        assert_eq!(
+            try_evaluate([num(16), call("sqrt", 1)].into_iter()),
+            Ok(crate::value::Value::Float(4.0))
+        );
+        assert_eq!(
+            try_evaluate([call("max", 0)].into_iter()),
+            Err(EvalError::WrongArgCount { name: "max".to_owned(), got: 0 })
+        );
+        assert_eq!(
+            try_evaluate([num(1), call("nope", 1)].into_iter()),
+            Err(EvalError::UnknownFunction("nope".to_owned()))
+        );
+        // more arguments than the balance counter holds
+        let spanned = [num(1), call("max", 300)].map(|tok| Ok(crate::tokens::Spanned::new(tok, Span::new(0, 1))));
+        let parsed = Parser::new(spanned.into_iter()).collect::<Result<Vec<_>, _>>();
+        assert_eq!(parsed, Err(ParseError::NotEnoughArgs(Span::new(0, 1))));
+        let mut wrapping = crate::Calculator::new().with_overflow(crate::OverflowPolicy::Wrapping);
+        assert_eq!(wrapping.try_calculate([num(i64::MIN), call("abs", 1)].into_iter()), Err(EvalError::Overflow));
+    }
+
+    #[test]
+    fn custom_functions() {
+        use crate::{
+            functions::{Arity, Functions},
+            value::Value,
+            Calculator,
+        };
+
+        let mut funcs = Functions::new();
+        funcs.register("answer", Arity::Exact(0), |_| Ok(Value::Int(Int::from(42))));
+        let tokens: Vec<_> = Parser::new(tokenize("answer() * 2")).collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens, vec![call("answer", 0), num(2), op('*')]);
+        assert_eq!(
+            Calculator::new().with_functions(&funcs).try_calculate(tokens.iter().cloned()),
+            Ok(Int::from(84))
+        );
+        assert_eq!(
+            try_calculate(tokens.into_iter()),
+            Err(EvalError::UnknownFunction("answer".to_owned()))
+        );
+    }
+
+    #[test]
+    fn custom_operators() {
+        use crate::{
+            ast::Expr,
+            grammar::{Fixity, Grammar, GrammarError},
+            tokens::{Assoc, Bitwise},
+            value::Value,
+            Calculator, OverflowPolicy, Printer,
+        };
+
+        fn int(val: &Value) -> Result<Int, EvalError> {
+            match val {
+                Value::Int(num) => Ok(*num),
+                _ => Err(EvalError::NotAnInteger),
+            }
+        }
+
+        let mut grammar = Grammar::new();
+        grammar.register("<=>", Fixity::Infix(Assoc::Left), 7, |args| {
+            let ord = args[0].partial_cmp(&args[1]).ok_or(EvalError::NotANumber)?;
+            Ok(Value::Int(Int::from(ord as i8)))
+        }).unwrap();
+        // symbols may be built at runtime
+        let word = String::from("mod");
+        let modulo = |args: &[Value]| Ok(Value::Int(int(&args[0])? % int(&args[1])?));
+        grammar.register(word, Fixity::Infix(Assoc::Left), 10, modulo).unwrap();
+        grammar.register("neg", Fixity::Prefix, 11, |args| {
+            Value::apply_unary(UnaryOperation::Neg, args[0].clone(), OverflowPolicy::Checked)
+        }).unwrap();
+        // takes over the builtin power
+        grammar.register("^", Fixity::Infix(Assoc::Left), 4, |args| {
+            Value::apply(Operation::Bit(Bitwise::Xor), args[0].clone(), args[1].clone(), OverflowPolicy::Checked)
+        }).unwrap();
+        grammar.register("@", Fixity::Prefix, 11, |args| Ok(Value::Int(int(&args[0])? * int(&args[0])?))).unwrap();
+        grammar.register("@", Fixity::Infix(Assoc::Left), 9, |args| {
+            Ok(Value::Int(int(&args[0])? * Int::from(10) + int(&args[1])?))
+        }).unwrap();
+
+        let parse = |input| {
+            Parser::new(tokenize(input).with_grammar(&grammar)).with_grammar(&grammar).collect::<Result<Vec<_>, _>>()
+        };
+        let tokens = parse("17 mod 5 + neg 2 ^ 3").unwrap();
+        assert_eq!(Printer(&tokens).to_string(), "17 5 mod 2 neg + 3 ^");
+        assert_eq!(Expr::from_rpn(tokens).unwrap().to_string(), "17 mod 5 + neg 2 ^ 3");
+
+        for (input, expected) in [
+            ("7 <=> 3 * 2", 1),
+            ("2 <=> 2 == 0 ? 10 : 20", 10),
+            ("17 mod 5 + 1", 3),
+            ("neg 2 ^ 3", -3),
+            ("2 ^ 3 ^ 1", 0),
+            ("@2 @ 3", 43),
+            ("1 @ @2", 14),
+            ("-4 ** 2", -16),
+        ] {
+            let tokens = parse(input).unwrap();
+            let res = Calculator::new().with_grammar(&grammar).try_calculate(tokens.into_iter());
+            assert_eq!(res, Ok(Int::from(expected)), "{input}");
+        }
+
+        assert_eq!(
+            try_calculate(parse("17 mod 5").unwrap().into_iter()),
+            Err(EvalError::UnknownOperator("mod".to_owned()))
+        );
+        // the builtin grammar only knows `<=` and `>`
+        assert_eq!(
+            Parser::new(tokenize("7 <=> 3")).collect::<Result<Vec<_>, _>>(),
+            Err(ParseError::NotEnoughArgs(Span::new(4, 5)))
+        );
+
+        // a symbol registered in one form keeps the builtin other one
+        let eval = |grammar: &Grammar, input| {
+            let tokens = Parser::new(tokenize(input).with_grammar(grammar)).with_grammar(grammar);
+            let tokens = tokens.collect::<Result<Vec<_>, _>>().unwrap();
+            Calculator::new().with_grammar(grammar).try_calculate(tokens.into_iter())
+        };
+        let mut prefix_only = Grammar::new();
+        prefix_only.register("-", Fixity::Prefix, 11, |args| Ok(Value::Int(int(&args[0])? * 100))).unwrap();
+        assert_eq!(eval(&prefix_only, "5 - 2"), Ok(3));
+        assert_eq!(eval(&prefix_only, "-3"), Ok(300));
+        assert_eq!(eval(&prefix_only, "2 * −3"), Ok(600));
+        let mut infix_only = Grammar::new();
+        let times = |args: &[Value]| Ok(Value::Int(int(&args[0])? * int(&args[1])?));
+        infix_only.register("-", Fixity::Infix(Assoc::Left), 9, times).unwrap();
+        assert_eq!(eval(&infix_only, "5 - 2"), Ok(10));
+        assert_eq!(eval(&infix_only, "-3"), Ok(-3));
+        assert_eq!(eval(&infix_only, "2 - -3"), Ok(-6));
+
+        let mut grammar = Grammar::new();
+        let noop = |_: &[Value]| Ok(Value::Int(0));
+        assert_eq!(grammar.register("", Fixity::Prefix, 11, noop), Err(GrammarError::InvalidSymbol(String::new())));
+        assert_eq!(
+            grammar.register("a+", Fixity::Infix(Assoc::Left), 9, noop),
+            Err(GrammarError::InvalidSymbol("a+".to_owned()))
+        );
+        assert_eq!(grammar.register("1x", Fixity::Prefix, 11, noop), Err(GrammarError::InvalidSymbol("1x".to_owned())));
+        assert_eq!(grammar.register("mod", Fixity::Prefix, 0, noop), Err(GrammarError::InvalidPrecedence(0)));
+        assert_eq!(
+            grammar.register("<=>", Fixity::Infix(Assoc::Left), -1, noop),
+            Err(GrammarError::InvalidPrecedence(-1))
+        );
+        // nothing was registered
+        assert_eq!(grammar.call("mod", &[Value::Int(1)]), Err(EvalError::UnknownOperator("mod".to_owned())));
+    }
+
     #[test]
     fn failures() {
         test_fallible(
             "1 2",
             vec![num(1), num(2)],
-            Err(ParseError::NotEnoughOps),
+            Err(ParseError::NotEnoughOps(Span::new(2, 3))),
             0,
         );
+        test_fallible("-", vec![op('-')], Err(ParseError::NotEnoughArgs(Span::new(0, 1))), 0);
+        test_fallible("1 +", vec![num(1), op('+')], Err(ParseError::NotEnoughArgs(Span::new(2, 3))), 0);
+        test_fallible("!", vec![unary('!')], Err(ParseError::NotEnoughArgs(Span::new(0, 1))), 0);
         test_fallible(
-            "1 +- 2",
-            vec![num(1), op('+'), op('-'), num(2)],
-            Err(ParseError::NotEnoughArgs),
+            "1 +* 2",
+            vec![num(1), op('+'), op('*'), num(2)],
+            Err(ParseError::NotEnoughArgs(Span::new(3, 4))),
             0,
         );
         test_fallible(
             "(1 + 2))",
             vec![LPAR, num(1), op('+'), num(2), RPAR, RPAR],
-            Err(ParseError::UnmatchedParens),
+            Err(ParseError::UnmatchedParens(Span::new(7, 8))),
             0,
         );
         test_fallible(
             "((1 + 2",
             vec![LPAR, LPAR, num(1), op('+'), num(2)],
-            Err(ParseError::UnmatchedParens),
+            Err(ParseError::UnmatchedParens(Span::new(1, 2))),
             0,
         );
+        for (input, err) in [
+            ("max(1,)", ParseError::NotEnoughArgs(Span::new(6, 7))),
+            ("max(, 1)", ParseError::NotEnoughArgs(Span::new(4, 5))),
+            ("max(1 +)", ParseError::NotEnoughArgs(Span::new(7, 8))),
+            ("2 max(1)", ParseError::NotEnoughOps(Span::new(2, 5))),
+            ("2 !", ParseError::NotEnoughOps(Span::new(2, 3))),
+            ("1 (+ x)", ParseError::NotEnoughOps(Span::new(2, 3))),
+            ("1 ()", ParseError::NotEnoughOps(Span::new(2, 3))),
+            ("x * 1 not y", ParseError::NotEnoughOps(Span::new(6, 9))),
+            ("1, 2", ParseError::UnexpectedComma(Span::new(1, 2))),
+            ("(1, 2)", ParseError::UnexpectedComma(Span::new(2, 3))),
+            ("max(1", ParseError::UnmatchedParens(Span::new(0, 4))),
+            ("1 + (2 * (3)", ParseError::UnmatchedParens(Span::new(4, 5))),
+        ] {
+            assert_eq!(Parser::new(tokenize(input)).collect::<Result<Vec<_>, _>>(), Err(err), "{input}");
+        }
         test_fallible(
-            "a + b",
+            "$a + b",
             vec![],
             Err(ParseError::Tokenization(TokenizeError::invalid_symbol(0))),
             0,
         );
     }
 
+    #[test]
+    fn eval_failures() {
+        assert_eq!(try_calculate([num(1), num(2)].into_iter()), Err(EvalError::LeftoverOperands));
+        assert_eq!(try_calculate([num(1), op('+')].into_iter()), Err(EvalError::StackUnderflow));
+        assert_eq!(try_calculate([LPAR, num(1), RPAR].into_iter()), Err(EvalError::UnexpectedParen));
+        assert_eq!(try_calculate([num(1), num(0), op('/')].into_iter()), Err(EvalError::DivisionByZero));
+    }
+
+    #[test]
+    fn floats() {
+        use crate::value::Value;
+
+        assert_eq!(eval("1.5 * 2"), Ok(Value::Float(3.0)));
+        assert_eq!(eval("1 / 4.0"), Ok(Value::Float(0.25)));
+        assert_eq!(eval("1 / 4"), Ok(Value::Int(Int::from(0))));
+        assert_eq!(eval("1 / 0.0"), Ok(Value::Float(f64::INFINITY)));
+        assert_eq!(try_calculate([float(0.5), num(2), op('*')].into_iter()), Err(EvalError::NotAnInteger));
+    }
+
+    #[test]
+    fn comparisons() {
+        use crate::value::Value;
+
+        assert_eq!(
+            Parser::new(tokenize("total * 2 > limit - 1")).collect::<Result<Vec<_>, _>>(),
+            Ok(vec![
+                Token::Ident("total".to_owned()),
+                num(2),
+                op('*'),
+                Token::Ident("limit".to_owned()),
+                num(1),
+                op('-'),
+                op('>'),
+            ])
+        );
+
+        assert_eq!(eval("2 * 3 > 5"), Ok(Value::Bool(true)));
+        assert_eq!(eval("1 + 1 <= 1"), Ok(Value::Bool(false)));
+        assert_eq!(eval("1 < 2 == 3 < 4"), Ok(Value::Bool(true)));
+        assert_eq!(eval("2 == 2.0"), Ok(Value::Bool(true)));
+        assert_eq!(eval("0.0 / 0 != 0.0 / 0"), Ok(Value::Bool(true)));
+        assert_eq!(eval("1 >= 0.0 / 0"), Ok(Value::Bool(false)));
+        assert_eq!(eval("(1 < 2) + 1"), Err(EvalError::NotANumber));
+        assert_eq!(eval("1 < 2 < 3"), Err(EvalError::NotANumber));
+        assert_eq!(eval("-(1 < 2)"), Err(EvalError::NotANumber));
+        assert_eq!(eval("max(1, 2 > 1)"), Err(EvalError::NotANumber));
+        assert_eq!(try_calculate([num(1), num(2), op('≤')].into_iter()), Err(EvalError::NotAnInteger));
+    }
+
+    #[test]
+    fn logic() {
+        use crate::value::Value;
+
+        assert_eq!(
+            Parser::new(tokenize("!a || b && not c")).collect::<Result<Vec<_>, _>>(),
+            Ok(vec![
+                Token::Ident("a".to_owned()),
+                unary('!'),
+                Token::Ident("b".to_owned()),
+                Token::Ident("c".to_owned()),
+                unary('!'),
+                op('&'),
+                op('|'),
+            ])
+        );
+
+        assert_eq!(eval("1 < 2 && 2 < 3"), Ok(Value::Bool(true)));
+        assert_eq!(eval("1 > 2 or 2 > 1"), Ok(Value::Bool(true)));
+        assert_eq!(eval("not (1 > 2) and !(2 < 1)"), Ok(Value::Bool(true)));
+        assert_eq!(eval("1 < 2 != 2 < 1 || 1 > 2"), Ok(Value::Bool(true)));
+        // right operand is not evaluated
+        assert_eq!(eval("1 > 2 && 1 / 0 > 0"), Ok(Value::Bool(false)));
+        assert_eq!(eval("1 < 2 || max(1 / 0, 1) > 0"), Ok(Value::Bool(true)));
+        assert_eq!(eval("(1 > 2 && 1 / 0 > 0) || 2 > 1"), Ok(Value::Bool(true)));
+        assert_eq!(eval("1 < 2 && 1 / 0 > 0"), Err(EvalError::DivisionByZero));
+        assert_eq!(eval("1 && 2"), Err(EvalError::NotABool));
+        assert_eq!(eval("1 < 2 && 2"), Err(EvalError::NotABool));
+        assert_eq!(eval("!1"), Err(EvalError::NotABool));
+        assert_eq!(eval("-(1 < 2 || 1 > 2)"), Err(EvalError::NotANumber));
+    }
+
+    #[test]
+    fn bitwise() {
+        use crate::{tokens::Bitwise, value::Value};
+
+        let bit = |bit| Token::Oper(Operation::Bit(bit));
+        assert_eq!(
+            Parser::new(tokenize("1 | 2 xor 3 & ~4 == 5 << 6 + 7")).collect::<Result<Vec<_>, _>>(),
+            Ok(vec![
+                num(1),
+                num(2),
+                num(3),
+                num(4),
+                unary('~'),
+                num(5),
+                num(6),
+                num(7),
+                op('+'),
+                bit(Bitwise::Shl),
+                op('='),
+                bit(Bitwise::And),
+                bit(Bitwise::Xor),
+                bit(Bitwise::Or),
+            ])
+        );
+
+        assert_eq!(eval("12 & 10 | 1"), Ok(Value::Int(9)));
+        assert_eq!(eval("12 xor 10"), Ok(Value::Int(6)));
+        assert_eq!(eval("1 << 4 | 1 << 0"), Ok(Value::Int(17)));
+        assert_eq!(eval("-8 >> 1"), Ok(Value::Int(-4)));
+        assert_eq!(eval("~0"), Ok(Value::Int(-1)));
+        assert_eq!(eval("~5 & 7"), Ok(Value::Int(2)));
+        assert_eq!(eval("1 << -1"), Err(EvalError::ShiftOutOfRange));
+        assert_eq!(eval("1.5 & 1"), Err(EvalError::BitwiseOnFloat));
+        assert_eq!(eval("~1.0"), Err(EvalError::BitwiseOnFloat));
+        assert_eq!(eval("(1 < 2) | 1"), Err(EvalError::NotANumber));
+        assert_eq!(eval("~(1 < 2)"), Err(EvalError::NotANumber));
+    }
+
+    #[test]
+    fn shift_range() {
+        use crate::value::Value;
+
+        assert_eq!(eval("1 << 63"), Ok(Value::Int(i64::MIN)));
+        assert_eq!(eval("3 << 63"), Ok(Value::Int(i64::MIN)));
+        assert_eq!(eval("1 << 64"), Err(EvalError::ShiftOutOfRange));
+        assert_eq!(eval("-1 >> 99999999999"), Err(EvalError::ShiftOutOfRange));
+    }
+
+    #[test]
+    fn conditional() {
+        use crate::value::Value;
+
+        let ident = |name: &str| Token::Ident(name.to_owned());
+        assert_eq!(
+            Parser::new(tokenize("a > 1 ? b ? 1 : 2 : c ? -d : e + 1")).collect::<Result<Vec<_>, _>>(),
+            Ok(vec![
+                ident("a"),
+                num(1),
+                op('>'),
+                ident("b"),
+                num(1),
+                num(2),
+                Token::Question,
+                ident("c"),
+                ident("d"),
+                unary('-'),
+                ident("e"),
+                num(1),
+                op('+'),
+                Token::Question,
+                Token::Question,
+            ])
+        );
+        for (input, err) in [
+            ("a ? b", ParseError::UnmatchedTernary(Span::new(2, 3))),
+            ("a : b", ParseError::UnmatchedTernary(Span::new(2, 3))),
+            ("(a ? b) : c", ParseError::UnmatchedTernary(Span::new(3, 4))),
+            ("(a) : c", ParseError::UnmatchedTernary(Span::new(4, 5))),
+            ("max(a ? b, c)", ParseError::UnmatchedTernary(Span::new(6, 7))),
+            ("a ? : b", ParseError::NotEnoughArgs(Span::new(4, 5))),
+            ("? a : b", ParseError::NotEnoughArgs(Span::new(0, 1))),
+            ("a ? b :", ParseError::NotEnoughArgs(Span::new(6, 7))),
+        ] {
+            assert_eq!(Parser::new(tokenize(input)).collect::<Result<Vec<_>, _>>(), Err(err), "{input}");
+        }
+
+        assert_eq!(eval("1 < 2 ? 10 : 20").map(|res| res.to_string()), Ok("10".to_owned()));
+        assert_eq!(eval("1 > 2 ? 10 : 1 > 3 ? 20 : 30").map(|res| res.to_string()), Ok("30".to_owned()));
+        assert_eq!(eval("(1 < 2 ? 1 > 2 : 1 < 2) ? 1 : 2.5"), Ok(Value::Float(2.5)));
+        assert_eq!(eval("2 * if(3 > 2, 4, 5) + 1").map(|res| res.to_string()), Ok("9".to_owned()));
+        // branch not taken is not evaluated
+        assert_eq!(eval("1 < 2 ? 1 : 1 / 0").map(|res| res.to_string()), Ok("1".to_owned()));
+        assert_eq!(eval("1 > 2 ? max(1 / 0, 1) : 2").map(|res| res.to_string()), Ok("2".to_owned()));
+        assert_eq!(eval("if(1 > 2, 1 / 0, if(1 < 2, 3, 1 % 0))").map(|res| res.to_string()), Ok("3".to_owned()));
+        assert_eq!(eval("1 < 2 ? 1 / 0 : 1"), Err(EvalError::DivisionByZero));
+        assert_eq!(eval("1 ? 2 : 3"), Err(EvalError::NotABool));
+        assert_eq!(eval("if(1, 2, 3)"), Err(EvalError::NotABool));
+        assert_eq!(
+            eval("if(1 < 2, 3)"),
+            Err(EvalError::WrongArgCount { name: "if".to_owned(), got: 2 })
+        );
+        assert_eq!(try_evaluate([num(1), num(2), Token::Question].into_iter()), Err(EvalError::StackUnderflow));
+    }
+
+    #[test]
+    fn radix_printing() {
+        use crate::Printer;
+
+        let toks = [num(255), num(-31), float(1.5), op('*'), op('+')];
+        assert_eq!(format!("{:#x}", Printer(&toks)), "0xff -0x1f 1.5 * +");
+        assert_eq!(format!("{:X}", Printer(&toks)), "FF -1F 1.5 * +");
+        assert_eq!(format!("{:#b}", Printer(&toks[..1])), "0b11111111");
+        assert_eq!(format!("{:o}", Printer(&toks[1..2])), "-37");
+
+        let value = try_evaluate([num(1), num(8), op('-')].into_iter()).unwrap();
+        assert_eq!(format!("{value:#o}"), "-0o7");
+        let value = try_evaluate([num(1), float(0.5), op('+')].into_iter()).unwrap();
+        assert_eq!(format!("{value:#x}"), "1.5");
+    }
+
+    #[test]
+    fn overflow_policy() {
+        use crate::{Calculator, OverflowPolicy};
+
+        assert_eq!(
+            try_calculate([num(i64::MAX), num(1), op('+')].into_iter()),
+            Err(EvalError::Overflow)
+        );
+        assert_eq!(
+            try_calculate([num(i64::MIN), num(-1), op('/')].into_iter()),
+            Err(EvalError::Overflow)
+        );
+        assert_eq!(
+            try_calculate([num(i64::MIN), unary('-')].into_iter()),
+            Err(EvalError::Overflow)
+        );
+
+        let eval = |overflow, toks: &[Token]| {
+            Calculator::new().with_overflow(overflow).try_calculate(toks.iter().cloned())
+        };
+
+        let add = [num(i64::MAX), num(1), op('+')];
+        assert_eq!(eval(OverflowPolicy::Checked, &add), Err(EvalError::Overflow));
+        assert_eq!(eval(OverflowPolicy::Wrapping, &add), Ok(i64::MIN));
+        assert_eq!(eval(OverflowPolicy::Saturating, &add), Ok(i64::MAX));
+
+        let div = [num(i64::MIN), num(-1), op('/')];
+        assert_eq!(eval(OverflowPolicy::Wrapping, &div), Ok(i64::MIN));
+        assert_eq!(eval(OverflowPolicy::Saturating, &div), Ok(i64::MAX));
+
+        let rem = [num(i64::MIN), num(-1), op('%')];
+        assert_eq!(eval(OverflowPolicy::Checked, &rem), Ok(0));
+        assert_eq!(eval(OverflowPolicy::Wrapping, &rem), Ok(0));
+        assert_eq!(eval(OverflowPolicy::Saturating, &rem), Ok(0));
+
+        let div_zero = [num(1), num(0), op('/')];
+        assert_eq!(eval(OverflowPolicy::Wrapping, &div_zero), Err(EvalError::DivisionByZero));
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn big_numbers() {
+        use crate::{tokens::BigInt, try_calculate_big, value::Value, Calculator, OverflowPolicy};
+
+        let big: BigInt = "99999999999999999999".parse().unwrap();
+        let tokens = rpn("99999999999999999999 * 10 + 9");
+        assert_eq!(try_calculate_big(tokens.iter().cloned()), Ok(&big * 10 + 9));
+        assert_eq!(try_calculate(tokens.into_iter()), Err(EvalError::Overflow));
+        // back to `Int` once it fits
+        let tokens = rpn("99999999999999999999 - 99999999999999999998");
+        assert_eq!(try_evaluate(tokens.into_iter()), Ok(Value::Int(1)));
+        assert_eq!(try_evaluate(rpn("99999999999999999999 > 1").into_iter()), Ok(Value::Bool(true)));
+        assert_eq!(try_evaluate(rpn("0x1_0000_0000_0000_0000 >> 64").into_iter()), Ok(Value::Int(1)));
+
+        let add = [num(i64::MAX), num(1), op('+')];
+        assert_eq!(try_calculate_big(add.iter().cloned()), Err(EvalError::Overflow));
+        let promoted = Calculator::new().with_overflow(OverflowPolicy::Promote).try_calculate_big(add.into_iter());
+        assert_eq!(promoted, Ok(BigInt::from(i64::MAX) + 1));
+        let neg = [num(i64::MIN), unary('-')];
+        let promoted = Calculator::new().with_overflow(OverflowPolicy::Promote).try_evaluate(neg.into_iter());
+        assert_eq!(promoted, Ok(Value::Big(-BigInt::from(i64::MIN))));
+
+        // too big to allocate
+        let promote = |input| {
+            Calculator::new().with_overflow(OverflowPolicy::Promote).try_evaluate(rpn(input).into_iter())
+        };
+        assert_eq!(promote("3 ^ 4000000"), Err(EvalError::Overflow));
+        assert_eq!(promote("2 ^ 64 << 4000000000"), Err(EvalError::Overflow));
+        assert_eq!(promote("2 ^ 64 << 2000000"), Err(EvalError::Overflow));
+        assert_eq!(promote("(-1) ^ 4000000001"), Ok(Value::Int(-1)));
+        assert_eq!(promote("0 ^ 4000000000"), Ok(Value::Int(0)));
+        let huge = BigInt::from(1) << 80u32;
+        assert_eq!(Operation::Pow.apply(BigInt::from(-1), huge.clone() + 1), Ok(BigInt::from(-1)));
+        assert_eq!(Operation::Pow.apply(BigInt::from(0), huge.clone()), Ok(BigInt::from(0)));
+        assert_eq!(Operation::Pow.apply(BigInt::from(2), huge), Err(EvalError::Overflow));
+        assert_eq!(promote("2 ^ 64 >> 4000000000"), Ok(Value::Int(0)));
+        assert!(matches!(promote("2 ^ 64 << 100000"), Ok(Value::Big(_))));
+    }
+
+    fn rpn(input: &str) -> Vec<Token> {
+        Parser::new(tokenize(input)).collect::<Result<_, _>>().unwrap()
+    }
+
+    fn eval(input: &str) -> Result<crate::value::Value, EvalError> {
+        try_evaluate(rpn(input).into_iter())
+    }
+
     fn test(input: &str, after_tokenize: Vec<Token>, after_parse: Vec<Token>, expected: i64) {
         test_fallible(input, after_tokenize, Ok(after_parse), expected);
     }
@@ -258,10 +1264,10 @@ mod tests {
         after_parse: Result<Vec<Token>, ParseError>,
         expected: i64,
     ) {
-        let collected: Vec<_> = match tokenize(input).collect() {
-            Ok(collected) => collected,
-            Err(_) => vec![],
-        };
+        let collected: Vec<_> = tokenize(input)
+            .map(|tok| tok.map(|tok| tok.value))
+            .collect::<Result<_, _>>()
+            .unwrap_or_default();
 
         assert_eq!(collected, after_tokenize);
 
@@ -285,6 +1291,58 @@ mod tests {
             }
         };
 
-        assert_eq!(calculate(collected.into_iter()), expected);
+        assert_eq!(calculate(collected.into_iter()), Int::from(expected));
+    }
+
+    #[test]
+    fn operations_out_of_domain() {
+        use crate::{
+            tokens::{Assoc, Bitwise, CustomOp},
+            OverflowPolicy,
+        };
+
+        let cmp = Operation::Cmp(Comparison::Lt);
+        assert_eq!(cmp.apply(1.0, 2.0), Err(EvalError::NotArithmetic));
+        assert_eq!(cmp.apply_int(1, 2, OverflowPolicy::Checked), Err(EvalError::NotArithmetic));
+
+        let and = Operation::Logic(Logic::And);
+        assert_eq!(and.apply(1.0, 2.0), Err(EvalError::NotABool));
+        assert_eq!(and.apply_int(1, 2, OverflowPolicy::Checked), Err(EvalError::NotABool));
+        assert_eq!(UnaryOperation::Not.apply(1.0), Err(EvalError::NotABool));
+
+        let shl = Operation::Bit(Bitwise::Shl);
+        assert_eq!(shl.apply(1.0, 2.0), Err(EvalError::NotArithmetic));
+        assert_eq!(shl.apply_int(1, 2, OverflowPolicy::Checked), Ok(4));
+        assert_eq!(UnaryOperation::BitNot.apply(1.0), Err(EvalError::NotArithmetic));
+
+        assert_eq!(Operation::Pow.apply(2, 62), Ok(1 << 62));
+        assert_eq!(Operation::Pow.apply(2, 63), Err(EvalError::Overflow));
+        assert_eq!(Operation::Pow.apply(2, -1), Err(EvalError::NegativeExponent));
+        assert_eq!(Operation::Pow.apply(1, 1 << 40), Ok(1));
+        assert_eq!(Operation::Pow.apply(-1, (1 << 40) + 1), Ok(-1));
+        assert_eq!(Operation::Pow.apply(0, 1 << 40), Ok(0));
+        assert_eq!(Operation::Pow.apply(2, 1 << 40), Err(EvalError::Overflow));
+        let huge = (1 << 40) + 1;
+        assert_eq!(Operation::Pow.apply_int(3, huge, OverflowPolicy::Checked), Err(EvalError::Overflow));
+        assert_eq!(Operation::Pow.apply_int(2, huge, OverflowPolicy::Wrapping), Ok(0));
+        let wrapped = 3i64.wrapping_pow(1 << 31).wrapping_pow(4);
+        assert_eq!(Operation::Pow.apply_int(3, 1 << 33, OverflowPolicy::Wrapping), Ok(wrapped));
+        assert_eq!(Operation::Pow.apply_int(-3, huge, OverflowPolicy::Saturating), Ok(i64::MIN));
+        assert_eq!(Operation::Pow.apply_int(-3, huge - 1, OverflowPolicy::Saturating), Ok(i64::MAX));
+        assert_eq!(Operation::Pow.apply_int(-1, huge, OverflowPolicy::Saturating), Ok(-1));
+
+        assert_eq!(Operation::Rem.apply(i64::MIN, -1), Ok(0));
+        assert_eq!(Operation::FloorDiv.apply(i64::MIN, -1), Ok(i64::MIN));
+        assert_eq!(Operation::Rem.apply(1, 0), Err(EvalError::DivisionByZero));
+        assert_eq!(Operation::FloorDiv.apply(-7, 0), Err(EvalError::DivisionByZero));
+        assert_eq!(Operation::Div.apply(1, 0), Err(EvalError::DivisionByZero));
+        assert_eq!(Operation::Rem.apply(-7.0, 0.0).map(f64::is_nan), Ok(true));
+
+        let custom = CustomOp { symbol: "<=>".into(), prio: 7, assoc: Assoc::Left };
+        let unknown = EvalError::UnknownOperator("<=>".to_owned());
+        assert_eq!(Operation::Custom(custom.clone()).apply(1.0, 2.0).unwrap_err(), unknown);
+        assert_eq!(Operation::Custom(custom.clone()).apply_int(1, 2, OverflowPolicy::Checked).unwrap_err(), unknown);
+        assert_eq!(UnaryOperation::Custom(custom.clone()).apply(1.0).unwrap_err(), unknown);
+        assert_eq!(UnaryOperation::Custom(custom).apply_int(1, OverflowPolicy::Wrapping).unwrap_err(), unknown);
     }
 }
@@ -1,24 +1,375 @@
-use std::io::{BufReader, BufRead};
+use std::{
+    fmt::{Binary, Display, LowerHex, Octal},
+    fs::File,
+    io::{self, BufRead, BufReader, IsTerminal, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
 
-use expr_parser::{parser, calculate, Printer};
+use clap::{Parser, ValueEnum};
+use expr_parser::{ast::Expr, diagnostics, notation, parser, recovery, rpn, value::Value, Calculator, Printer};
 
-fn main() {
-    let reader = BufReader::new(std::io::stdin());
+mod repl;
 
-    reader.lines()
-        .filter_map(|mb_line| match mb_line {
-            Ok(line) => Some(line),
-            Err(err) => {
-                eprintln!("Couldn't read line: {err}");
-                None
-            },
-        })
-        .for_each(|line| match parser::parse(&line).collect::<Result<Vec<_>, _>>() {
-            Ok(tokens) => {
-                println!("{} = {}", Printer(&tokens), calculate(tokens.iter().copied()));
-            },
+/// Evaluates arithmetic expressions, one per line
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// Expression to evaluate, may be repeated
+    #[arg(short = 'e', long = "expr", value_name = "EXPR")]
+    exprs: Vec<String>,
+    /// Files with an expression per line, `-` for stdin; stdin is read when there are no other inputs
+    files: Vec<PathBuf>,
+    #[command(flatten)]
+    format: Format,
+    /// Stop at the first expression that fails to parse or evaluate
+    #[arg(long)]
+    fail_fast: bool,
+}
+
+#[derive(Debug, Clone, Copy, clap::Args)]
+pub struct Format {
+    /// Notation expressions are written in
+    #[arg(short, long, value_enum, default_value_t = Input::Infix)]
+    input: Input,
+    /// What to print for each expression, `RPN = value` by default
+    #[arg(short, long, value_enum)]
+    output: Option<Output>,
+    /// Base integers are printed in, the JSON value stays a number
+    #[arg(short, long, value_enum, default_value_t = Radix::Dec)]
+    radix: Radix,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Input {
+    /// Usual notation, like `1 + 2 * 3`
+    Infix,
+    /// Postfix, like `1 2 3 * +`, calls are written as `max/2`
+    Rpn,
+    /// Prefix, like `+ 1 * 2 3`
+    Prefix,
+    /// S-expressions, like `(+ 1 (* 2 3))`
+    Sexpr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    /// Postfix form
+    Rpn,
+    /// Normalised expression, with only the necessary parentheses
+    Infix,
+    /// Prefix form
+    Prefix,
+    /// S-expression
+    Sexpr,
+    /// Result of evaluation
+    Value,
+    /// Object with the input, postfix form and result or errors
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Radix {
+    /// Decimal
+    Dec,
+    /// Hexadecimal, with the `0x` prefix like literals
+    Hex,
+    /// Octal, with `0o`
+    Oct,
+    /// Binary, with `0b`
+    Bin,
+}
+
+impl Radix {
+    fn show<T: Display + Binary + Octal + LowerHex>(self, item: &T) -> String {
+        match self {
+            Radix::Dec => item.to_string(),
+            Radix::Hex => format!("{item:#x}"),
+            Radix::Oct => format!("{item:#o}"),
+            Radix::Bin => format!("{item:#b}"),
+        }
+    }
+}
+
+/// Counts expressions to report them at the end
+#[derive(Debug, Default)]
+struct Summary {
+    total: usize,
+    failed: usize,
+}
+
+/// Evaluates expressions one by one, writing results to `out` and errors to `err_out`
+struct Runner<O, E> {
+    format: Format,
+    fail_fast: bool,
+    summary: Summary,
+    /// A file couldn't be opened or read
+    unreadable: bool,
+    /// `--fail-fast` stopped at a failed expression
+    stopped: bool,
+    out: O,
+    err_out: E,
+}
+
+impl<O: Write, E: Write> Runner<O, E> {
+    fn new(format: Format, fail_fast: bool, out: O, err_out: E) -> Self {
+        Self { format, fail_fast, summary: Summary::default(), unreadable: false, stopped: false, out, err_out }
+    }
+
+    /// Evaluates an expression, blank lines are skipped. `false` once `--fail-fast` stops the run
+    fn line(&mut self, line: &str) -> io::Result<bool> {
+        if line.trim().is_empty() {
+            return Ok(true);
+        }
+        self.summary.total += 1;
+        if !evaluate(line, self.format, &mut self.out, &mut self.err_out)? {
+            self.summary.failed += 1;
+            self.stopped = self.fail_fast;
+        }
+        Ok(!self.stopped)
+    }
+
+    /// Evaluates every line of `reader`, read errors are reported and skipped
+    fn read(&mut self, reader: impl BufRead) -> io::Result<bool> {
+        for mb_line in reader.lines() {
+            match mb_line {
+                Ok(line) => if !self.line(&line)? {
+                    return Ok(false);
+                },
+                Err(err) => {
+                    writeln!(self.err_out, "Couldn't read line: {err}")?;
+                    self.unreadable = true;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Reports failures, `true` if there were none
+    fn finish(&mut self) -> io::Result<bool> {
+        let Summary { total, failed } = self.summary;
+        if failed != 0 {
+            let stopped = if self.stopped { ", stopped at the first one" } else { "" };
+            writeln!(self.err_out, "{failed} of {total} expressions failed{stopped}")?;
+        }
+        Ok(failed == 0 && !self.unreadable)
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    if args.exprs.is_empty() && args.files.is_empty() && std::io::stdin().is_terminal() {
+        return match repl::run(args.format) {
+            Ok(()) => ExitCode::SUCCESS,
             Err(err) => {
-                eprintln!("Couldn't parse \"{line}\": {err}");
+                eprintln!("Couldn't run interactive session: {err}");
+                ExitCode::FAILURE
             }
-        })
+        };
+    }
+
+    let mut runner = Runner::new(args.format, args.fail_fast, io::stdout().lock(), io::stderr().lock());
+    match run(&args, &mut runner).and_then(|()| runner.finish()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("Couldn't write output: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Evaluates the expressions of `args`, then its files
+fn run<O: Write, E: Write>(args: &Args, runner: &mut Runner<O, E>) -> io::Result<()> {
+    for expr in &args.exprs {
+        if !runner.line(expr)? {
+            return Ok(());
+        }
+    }
+
+    let stdin = [PathBuf::from("-")];
+    let files = if args.files.is_empty() && args.exprs.is_empty() { &stdin[..] } else { &args.files[..] };
+    for path in files {
+        let reader: Box<dyn BufRead> = if path.as_os_str() == "-" {
+            Box::new(BufReader::new(std::io::stdin()))
+        } else {
+            match File::open(path) {
+                Ok(file) => Box::new(BufReader::new(file)),
+                Err(err) => {
+                    writeln!(runner.err_out, "Couldn't open {}: {err}", path.display())?;
+                    runner.unreadable = true;
+                    continue;
+                }
+            }
+        };
+        if !runner.read(reader)? {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+/// Writes `line` in the chosen form to `out`, or its errors to `err_out`, `false` if it failed
+fn evaluate(
+    line: &str,
+    Format { input, output, radix }: Format,
+    out: &mut impl Write,
+    err_out: &mut impl Write,
+) -> io::Result<bool> {
+    let parsed = match input {
+        Input::Infix => parser::parse(line).collect::<Result<Vec<_>, _>>(),
+        Input::Rpn => rpn::parse_rpn(line).collect(),
+        Input::Prefix => notation::parse_prefix(line).map(|expr| expr.to_rpn()),
+        Input::Sexpr => notation::parse_sexpr(line).map(|expr| expr.to_rpn()),
+    };
+    let tokens = match parsed {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            let errors = match input {
+                // show every mistake at once, not just the first one
+                Input::Infix => recovery::parse_recovering(line).errors,
+                Input::Rpn | Input::Prefix | Input::Sexpr => vec![err],
+            };
+            if output == Some(Output::Json) {
+                let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+                writeln!(out, "{}", serde_json::json!({ "input": line, "errors": errors }))?;
+            } else {
+                for err in errors {
+                    write!(err_out, "{}", diagnostics::render_parse_error(line, &err))?;
+                }
+            }
+            return Ok(false);
+        }
+    };
+
+    match output {
+        Some(Output::Rpn) => {
+            writeln!(out, "{}", radix.show(&Printer(&tokens)))?;
+            return Ok(true);
+        }
+        Some(format @ (Output::Infix | Output::Prefix | Output::Sexpr)) => {
+            // every input notation checks arity already, so this only catches a gap in some parser
+            let expr = match Expr::from_rpn(tokens) {
+                Ok(expr) => expr,
+                Err(err) => {
+                    // it holds token indices, nothing to point at in `line`
+                    writeln!(err_out, "Couldn't build a tree of \"{line}\": {err}")?;
+                    return Ok(false);
+                }
+            };
+            match format {
+                Output::Prefix => writeln!(out, "{}", notation::Prefix(&expr))?,
+                Output::Sexpr => writeln!(out, "{}", notation::SExpr(&expr))?,
+                _ => writeln!(out, "{expr}")?,
+            }
+            return Ok(true);
+        }
+        _ => (),
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    let mut calc = Calculator::new();
+    // integers of any size, when built with them
+    #[cfg(feature = "bigint")]
+    let mut calc = Calculator::new().with_overflow(expr_parser::OverflowPolicy::Promote);
+    match (calc.try_evaluate(tokens.iter().cloned()), output) {
+        (Ok(res), Some(Output::Json)) => {
+            let rpn = radix.show(&Printer(&tokens));
+            writeln!(out, "{}", serde_json::json!({ "input": line, "rpn": rpn, "value": json_value(&res) }))?;
+            Ok(true)
+        }
+        (Ok(res), Some(Output::Value)) => {
+            writeln!(out, "{}", radix.show(&res))?;
+            Ok(true)
+        }
+        (Ok(res), _) => {
+            writeln!(out, "{} = {}", radix.show(&Printer(&tokens)), radix.show(&res))?;
+            Ok(true)
+        }
+        (Err(err), Some(Output::Json)) => {
+            let rpn = radix.show(&Printer(&tokens));
+            writeln!(out, "{}", serde_json::json!({ "input": line, "rpn": rpn, "errors": [err.to_string()] }))?;
+            Ok(false)
+        }
+        (Err(err), _) => {
+            writeln!(err_out, "Couldn't calculate \"{line}\": {err}")?;
+            Ok(false)
+        }
+    }
+}
+
+/// Numbers that don't fit into JSON ones are written as strings
+fn json_value(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Int(num) => (*num).into(),
+        Value::Float(num) => serde_json::Number::from_f64(*num).map_or(serde_json::Value::Null, Into::into),
+        Value::Bool(flag) => (*flag).into(),
+        // big integers
+        _ => value.to_string().into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::{run, Args, Runner};
+
+    /// Runs the expressions of `args` like `main`, gives the exit status, output and errors
+    fn batch(args: &[&str]) -> (bool, String, String) {
+        let args = Args::parse_from(["expr-parser"].iter().chain(args));
+        let mut runner = Runner::new(args.format, args.fail_fast, Vec::new(), Vec::new());
+        run(&args, &mut runner).unwrap();
+        let ok = runner.finish().unwrap();
+        (ok, String::from_utf8(runner.out).unwrap(), String::from_utf8(runner.err_out).unwrap())
+    }
+
+    #[test]
+    fn exit_status_and_summary() {
+        assert_eq!(batch(&["-e", "1 + 2", "-e", "2 * 3"]), (true, "1 2 + = 3\n2 3 * = 6\n".to_owned(), String::new()));
+        assert_eq!(batch(&["-o", "value", "-r", "hex", "-e", "255"]), (true, "0xff\n".to_owned(), String::new()));
+
+        // blank lines aren't counted
+        let (ok, out, err_out) = batch(&["-e", "1 / 0", "-e", "1 +", "-e", " ", "-e", "4"]);
+        assert!(!ok);
+        assert_eq!(out, "4 = 4\n");
+        assert!(err_out.starts_with("Couldn't calculate \"1 / 0\": division by zero\n"), "{err_out}");
+        assert!(err_out.ends_with("\n2 of 3 expressions failed\n"), "{err_out}");
+
+        let (ok, out, err_out) = batch(&["-o", "json", "-e", "1 / 0"]);
+        assert!(!ok);
+        assert_eq!(out, "{\"errors\":[\"division by zero\"],\"input\":\"1 / 0\",\"rpn\":\"1 0 /\"}\n");
+        assert_eq!(err_out, "1 of 1 expressions failed\n");
+    }
+
+    #[test]
+    fn fail_fast() {
+        let (ok, out, err_out) = batch(&["--fail-fast", "-e", "1", "-e", "1 / 0", "-e", "2"]);
+        assert!(!ok);
+        assert_eq!(out, "1 = 1\n");
+        assert!(err_out.ends_with("\n1 of 2 expressions failed, stopped at the first one\n"), "{err_out}");
+
+        let args = Args::parse_from(["expr-parser", "--fail-fast"]);
+        let mut runner = Runner::new(args.format, args.fail_fast, Vec::new(), Vec::new());
+        assert!(!runner.read(&b"1\n\n(2\n3\n"[..]).unwrap());
+        assert_eq!((runner.summary.total, runner.summary.failed), (2, 1));
+        assert_eq!(String::from_utf8(runner.out).unwrap(), "1 = 1\n");
+    }
+
+    #[test]
+    fn files() {
+        let (ok, out, err_out) = batch(&["-e", "1", "/nonexistent/exprs.txt"]);
+        // nothing failed to evaluate, but the file couldn't be read
+        assert!(!ok);
+        assert_eq!(out, "1 = 1\n");
+        assert!(err_out.starts_with("Couldn't open /nonexistent/exprs.txt: "), "{err_out}");
+        assert!(!err_out.contains("expressions failed"), "{err_out}");
+
+        let args = Args::parse_from(["expr-parser", "-o", "rpn"]);
+        let mut runner = Runner::new(args.format, args.fail_fast, Vec::new(), Vec::new());
+        assert!(runner.read(&b"1 + 2\n\nmax(1, 2)\n"[..]).unwrap());
+        assert!(runner.finish().unwrap());
+        assert_eq!(String::from_utf8(runner.out).unwrap(), "1 2 +\n1 2 max/2\n");
+    }
 }
@@ -0,0 +1,248 @@
+use std::{fmt::Display, iter::Peekable};
+
+use crate::{
+    ast::Expr,
+    parser::ParseError,
+    rpn::Glued,
+    tokenizer::tokenize,
+    tokens::{Paren, Span, Spanned, Token, UnaryOperation},
+};
+
+/// Prefix (Polish) form: `+ 1 * 2 3`, unary operators and calls are written
+/// as in [`crate::Printer`] output: `u- x`, `max/2 a b`
+pub struct Prefix<'e>(pub &'e Expr);
+
+impl Display for Prefix<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Expr::Num(num) => f.write_fmt(format_args!("{num}")),
+            Expr::Var(name) => f.write_str(name),
+            Expr::Unary { op, arg } => f.write_fmt(format_args!("{op} {}", Prefix(arg))),
+            Expr::Binary { op, lhs, rhs } => f.write_fmt(format_args!("{op} {} {}", Prefix(lhs), Prefix(rhs))),
+            Expr::Call { name, args } => {
+                f.write_fmt(format_args!("{name}/{}", args.len()))?;
+                args.iter().try_for_each(|arg| f.write_fmt(format_args!(" {}", Prefix(arg))))
+            }
+            Expr::Cond { cond, then, otherwise } => {
+                f.write_fmt(format_args!("? {} {} {}", Prefix(cond), Prefix(then), Prefix(otherwise)))
+            }
+            Expr::Group(inner) => Prefix(inner).fmt(f),
+        }
+    }
+}
+
+/// S-expression form: `(+ 1 (* 2 3))`, unary operators take one argument
+/// like in `(- x)`, calls are lists headed by the name: `(max a b)`
+pub struct SExpr<'e>(pub &'e Expr);
+
+impl Display for SExpr<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Expr::Num(num) => f.write_fmt(format_args!("{num}")),
+            Expr::Var(name) => f.write_str(name),
+            Expr::Unary { op, arg } => f.write_fmt(format_args!("({} {})", op.symbol(), SExpr(arg))),
+            Expr::Binary { op, lhs, rhs } => f.write_fmt(format_args!("({op} {} {})", SExpr(lhs), SExpr(rhs))),
+            Expr::Call { name, args } => {
+                f.write_fmt(format_args!("({name}"))?;
+                args.iter().try_for_each(|arg| f.write_fmt(format_args!(" {}", SExpr(arg))))?;
+                f.write_str(")")
+            }
+            Expr::Cond { cond, then, otherwise } => {
+                f.write_fmt(format_args!("(? {} {} {})", SExpr(cond), SExpr(then), SExpr(otherwise)))
+            }
+            Expr::Group(inner) => SExpr(inner).fmt(f),
+        }
+    }
+}
+
+/// Reads what [`Prefix`] writes
+pub fn parse_prefix(input: &str) -> Result<Expr, ParseError> {
+    let mut tokens = Glued::new(tokenize(input))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(ParseError::Tokenization)?
+        .into_iter();
+    let expr = prefix(&mut tokens, None)?;
+    match tokens.next() {
+        Some(extra) => Err(ParseError::NotEnoughOps(extra.span)),
+        None => Ok(expr),
+    }
+}
+
+/// `owner` is the operator waiting for this argument
+fn prefix(tokens: &mut impl Iterator<Item = Spanned<Token>>, owner: Option<Span>) -> Result<Expr, ParseError> {
+    let Some(Spanned { value, span }) = tokens.next() else {
+        return Err(owner.map_or(ParseError::Empty, ParseError::NotEnoughArgs));
+    };
+    let expr = match value {
+        Token::Number(num) => Expr::Num(num),
+        Token::Ident(name) => Expr::Var(name),
+        Token::UnaryOper(op) => Expr::Unary { op, arg: Box::new(prefix(tokens, Some(span))?) },
+        Token::Oper(op) => {
+            let lhs = prefix(tokens, Some(span))?;
+            let rhs = prefix(tokens, Some(span))?;
+            Expr::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs) }
+        }
+        Token::Call { name, arity } => {
+            let args = (0..arity).map(|_| prefix(tokens, Some(span))).collect::<Result<_, _>>()?;
+            Expr::Call { name, args }
+        }
+        Token::Question => {
+            let cond = prefix(tokens, Some(span))?;
+            let then = prefix(tokens, Some(span))?;
+            let otherwise = prefix(tokens, Some(span))?;
+            Expr::Cond { cond: Box::new(cond), then: Box::new(then), otherwise: Box::new(otherwise) }
+        }
+        Token::Comma => return Err(ParseError::UnexpectedComma(span)),
+        Token::Colon => return Err(ParseError::UnmatchedTernary(span)),
+        // grouping is meaningless in prefix form
+        Token::Paren(_) => return Err(ParseError::UnmatchedParens(span)),
+    };
+    Ok(expr)
+}
+
+/// Reads what [`SExpr`] writes
+pub fn parse_sexpr(input: &str) -> Result<Expr, ParseError> {
+    let mut tokens = tokenize(input)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(ParseError::Tokenization)?
+        .into_iter()
+        .peekable();
+    let (expr, _) = sexpr(&mut tokens, None)?;
+    match tokens.next() {
+        Some(Spanned { value: Token::Paren(Paren::Right), span }) => Err(ParseError::UnmatchedParens(span)),
+        Some(extra) => Err(ParseError::NotEnoughOps(extra.span)),
+        None => Ok(expr),
+    }
+}
+
+/// Expression with its span, `open` is the paren of the enclosing list
+fn sexpr<I>(tokens: &mut Peekable<I>, open: Option<Span>) -> Result<(Expr, Span), ParseError>
+where
+    I: Iterator<Item = Spanned<Token>>,
+{
+    let Some(Spanned { value, span }) = tokens.next() else {
+        return Err(open.map_or(ParseError::Empty, ParseError::UnmatchedParens));
+    };
+    match value {
+        Token::Number(num) => Ok((Expr::Num(num), span)),
+        Token::Ident(name) => Ok((Expr::Var(name), span)),
+        Token::Paren(Paren::Left) => list(tokens, span),
+        Token::Paren(Paren::Right) => Err(ParseError::UnmatchedParens(span)),
+        Token::Comma => Err(ParseError::UnexpectedComma(span)),
+        Token::Colon => Err(ParseError::UnmatchedTernary(span)),
+        // outside of a list there is nothing to apply it to
+        Token::Oper(_) | Token::UnaryOper(_) | Token::Question | Token::Call { .. } => {
+            Err(ParseError::NotEnoughArgs(span))
+        }
+    }
+}
+
+/// List after its opening paren
+fn list<I>(tokens: &mut Peekable<I>, open: Span) -> Result<(Expr, Span), ParseError>
+where
+    I: Iterator<Item = Spanned<Token>>,
+{
+    let Some(Spanned { value: head, span: head_span }) = tokens.next() else {
+        return Err(ParseError::UnmatchedParens(open));
+    };
+    if head == Token::Paren(Paren::Right) {
+        return Err(ParseError::NotEnoughArgs(head_span));
+    }
+    let mut args = vec![];
+    let close = loop {
+        match tokens.peek() {
+            Some(Spanned { value: Token::Paren(Paren::Right), span }) => {
+                let close = *span;
+                tokens.next();
+                break close;
+            }
+            Some(_) => args.push(sexpr(tokens, Some(open))?),
+            None => return Err(ParseError::UnmatchedParens(open)),
+        }
+    };
+    let span = open.join(close);
+
+    let expr = match head {
+        Token::Oper(op) => match <[(Expr, Span); 2]>::try_from(args) {
+            Ok([(lhs, _), (rhs, _)]) => Expr::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs) },
+            Err(mut args) => match (args.len(), UnaryOperation::from_binary(&op)) {
+                (1, Some(op)) => Expr::Unary { op, arg: Box::new(args.pop().unwrap().0) },
+                (0 | 1, _) => return Err(ParseError::NotEnoughArgs(head_span)),
+                _ => return Err(ParseError::NotEnoughOps(args[2].1)),
+            },
+        },
+        Token::UnaryOper(op) => match <[(Expr, Span); 1]>::try_from(args) {
+            Ok([(arg, _)]) => Expr::Unary { op, arg: Box::new(arg) },
+            Err(args) if args.is_empty() => return Err(ParseError::NotEnoughArgs(head_span)),
+            Err(args) => return Err(ParseError::NotEnoughOps(args[1].1)),
+        },
+        Token::Question => match <[(Expr, Span); 3]>::try_from(args) {
+            Ok([(cond, _), (then, _), (otherwise, _)]) => {
+                Expr::Cond { cond: Box::new(cond), then: Box::new(then), otherwise: Box::new(otherwise) }
+            }
+            Err(args) if args.len() < 3 => return Err(ParseError::NotEnoughArgs(head_span)),
+            Err(args) => return Err(ParseError::NotEnoughOps(args[3].1)),
+        },
+        Token::Ident(name) => Expr::Call { name, args: args.into_iter().map(|(arg, _)| arg).collect() },
+        Token::Comma => return Err(ParseError::UnexpectedComma(head_span)),
+        Token::Colon => return Err(ParseError::UnmatchedTernary(head_span)),
+        // a list has to start with what to apply
+        Token::Number(_) | Token::Paren(_) | Token::Call { .. } => {
+            return Err(ParseError::NotEnoughOps(head_span))
+        }
+    };
+    Ok((expr, span))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ast::Expr, parser::ParseError, tokens::Span};
+
+    use super::{parse_prefix, parse_sexpr, Prefix, SExpr};
+
+    #[test]
+    fn round_trip() {
+        for (input, prefix, sexpr) in [
+            ("1 + 2 * 3", "+ 1 * 2 3", "(+ 1 (* 2 3))"),
+            ("-(x - 1) ^ 2", "u- ^ - x 1 2", "(- (^ (- x 1) 2))"),
+            ("max(1, +a // 2.5, f())", "max/3 1 // u+ a 2.5 f/0", "(max 1 (// (+ a) 2.5) (f))"),
+            ("!(a < 1) || b && c", "|| ! < a 1 && b c", "(|| (! (< a 1)) (&& b c))"),
+            ("a ? b : c ? 1 : -2", "? a b ? c 1 u- 2", "(? a b (? c 1 (- 2)))"),
+        ] {
+            let expr = Expr::parse(input).unwrap().ungrouped();
+            assert_eq!(Prefix(&expr).to_string(), prefix);
+            assert_eq!(SExpr(&expr).to_string(), sexpr);
+            assert_eq!(parse_prefix(prefix).as_ref(), Ok(&expr), "{prefix}");
+            assert_eq!(parse_sexpr(sexpr).as_ref(), Ok(&expr), "{sexpr}");
+        }
+        assert_eq!(parse_sexpr("  (*\n  (+ 1 2)\n  3)"), Expr::parse("(1 + 2) * 3").map(Expr::ungrouped));
+    }
+
+    #[test]
+    fn failures() {
+        for (input, err) in [
+            ("", ParseError::Empty),
+            ("+ 1", ParseError::NotEnoughArgs(Span::new(0, 1))),
+            ("max/2 1", ParseError::NotEnoughArgs(Span::new(0, 5))),
+            ("* 1 2 3", ParseError::NotEnoughOps(Span::new(6, 7))),
+            ("+ (1) 2", ParseError::UnmatchedParens(Span::new(2, 3))),
+        ] {
+            assert_eq!(parse_prefix(input), Err(err), "{input}");
+        }
+        for (input, err) in [
+            ("", ParseError::Empty),
+            ("(+ 1 (* 2 3)", ParseError::UnmatchedParens(Span::new(0, 1))),
+            ("(+ 1 2))", ParseError::UnmatchedParens(Span::new(7, 8))),
+            ("(* 1)", ParseError::NotEnoughArgs(Span::new(1, 2))),
+            ("(- 1 2 3)", ParseError::NotEnoughOps(Span::new(7, 8))),
+            ("(1 2)", ParseError::NotEnoughOps(Span::new(1, 2))),
+            ("()", ParseError::NotEnoughArgs(Span::new(1, 2))),
+            ("1 2", ParseError::NotEnoughOps(Span::new(2, 3))),
+            ("(max 1, 2)", ParseError::UnexpectedComma(Span::new(6, 7))),
+            ("(! a b)", ParseError::NotEnoughOps(Span::new(5, 6))),
+            ("(? a b)", ParseError::NotEnoughArgs(Span::new(1, 2))),
+        ] {
+            assert_eq!(parse_sexpr(input), Err(err), "{input}");
+        }
+    }
+}
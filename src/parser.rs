@@ -1,67 +1,184 @@
-use std::fmt::{Debug, Display};
+use std::{fmt::{Debug, Display}, iter::Peekable};
 
-use crate::{tokens, TokenVisitor, tokenizer::{TokenizeError, Tokenizer, tokenize}};
+use crate::{grammar::{self, Grammar}, tokens, TokenVisitor, tokenizer::{TokenizeError, Tokenizer, tokenize}};
 
-pub struct Parser<I> {
-    inner: I,
+pub struct Parser<'g, I: Iterator> {
+    /// Peeked to tell a function call from a variable
+    inner: Peekable<I>,
     stack: Vec<Stacked>,
     curr: Option<tokens::Token>,
+    /// Span of `curr`, or of the last token once the input is over
+    curr_span: tokens::Span,
     state: State,
     /// +1 on argument, -1 on operator, can't be out of [0, 1] for valid infix string
     arg_balance: i8,
     par_balance: i32,
+    /// Tells which custom operators have a prefix form
+    grammar: &'g Grammar,
+    /// Tokens given out so far
+    emitted: usize,
+    /// Values of `emitted` at each closed explicit paren, the group is the last expression by then
+    groups: Vec<usize>,
 }
 
-pub fn parse(input: &str) -> Parser<Tokenizer> {
+pub fn parse(input: &str) -> Parser<'_, Tokenizer<'_>> {
     Parser::new(tokenize(input))
 }
 
+/// Spans point at the offending token: the unmatched paren, the operator
+/// or the argument that broke the balance
 #[derive(Debug, PartialEq, Eq)]
 pub enum ParseError {
     Tokenization(TokenizeError),
-    UnmatchedParens,
-    NotEnoughArgs,
-    NotEnoughOps,
+    UnmatchedParens(tokens::Span),
+    NotEnoughArgs(tokens::Span),
+    NotEnoughOps(tokens::Span),
+    /// Comma outside of function call arguments
+    UnexpectedComma(tokens::Span),
+    /// `?` without its `:` or the other way round
+    UnmatchedTernary(tokens::Span),
+    /// Nothing to build an expression from
+    Empty,
+}
+
+impl ParseError {
+    /// Where the error is, tokenization errors get an empty span at their position
+    pub fn span(&self) -> Option<tokens::Span> {
+        match self {
+            ParseError::Tokenization(tok_err) => Some(tokens::Span::new(tok_err.at(), tok_err.at())),
+            ParseError::UnmatchedParens(span)
+            | ParseError::NotEnoughArgs(span)
+            | ParseError::NotEnoughOps(span)
+            | ParseError::UnexpectedComma(span)
+            | ParseError::UnmatchedTernary(span) => Some(*span),
+            ParseError::Empty => None,
+        }
+    }
 }
 
 impl Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ParseError::Tokenization(tok_err) => f.write_fmt(format_args!("TokenizeError: {tok_err}")),
-            ParseError::UnmatchedParens => f.write_str("unmatched parens"),
-            ParseError::NotEnoughArgs => f.write_str("got operators without arguments"),
-            ParseError::NotEnoughOps => f.write_str("got arguments without operator"),
+            ParseError::UnmatchedParens(span) => f.write_fmt(format_args!("unmatched parens at {}", span.start)),
+            ParseError::NotEnoughArgs(span) => {
+                f.write_fmt(format_args!("got operators without arguments at {}", span.start))
+            }
+            ParseError::NotEnoughOps(span) => {
+                f.write_fmt(format_args!("got arguments without operator at {}", span.start))
+            }
+            ParseError::UnexpectedComma(span) => {
+                f.write_fmt(format_args!("got comma outside of function call at {}", span.start))
+            }
+            ParseError::UnmatchedTernary(span) => f.write_fmt(format_args!("unmatched `?` or `:` at {}", span.start)),
+            ParseError::Empty => f.write_str("empty expression"),
         }
     }
 }
 
 impl std::error::Error for ParseError {}
 
-impl<I> Parser<I> {
+impl<'g, I: Iterator> Parser<'g, I> {
     pub fn new(inner: I) -> Self {
         Self {
-            inner,
+            inner: inner.peekable(),
             stack: vec![],
             curr: None,
+            curr_span: tokens::Span::default(),
             state: State::Skip,
             arg_balance: 0,
             par_balance: 0,
+            grammar: grammar::builtin(),
+            emitted: 0,
+            groups: vec![],
         }
     }
 
+    /// Should be the grammar the tokens come from, see [`Tokenizer::with_grammar`]
+    pub fn with_grammar(self, grammar: &'g Grammar) -> Self {
+        Self { grammar, ..self }
+    }
+
     fn token_from_state(&mut self) -> Option<Result<tokens::Token, ParseError>> {
         let mb_token = match self.state {
             State::PopParenLevel => match self.stack.pop() {
-                Some(Stacked::LBrace) => { self.curr = None; self.state = State::Skip; None },
+                Some(Stacked::LBrace(_)) => {
+                    self.groups.push(self.emitted);
+                    self.curr = None;
+                    self.state = State::Skip;
+                    None
+                },
+                Some(Stacked::Op(op)) => {
+                    Some(tokens::Token::Oper(op))
+                },
+                Some(Stacked::Unary(op)) => Some(tokens::Token::UnaryOper(op)),
+                Some(Stacked::Colon) => Some(tokens::Token::Question),
+                Some(Stacked::Question(span)) => return Some(Err(ParseError::UnmatchedTernary(span))),
+                Some(Stacked::Call { name, commas, .. }) => {
+                    // `f()` is the only case of `)` right after nothing
+                    let arity = if self.arg_balance == 0 { 0 } else { commas + 1 };
+                    self.arg_balance = 1;
+                    self.curr = None;
+                    self.state = State::Skip;
+                    Some(tokens::Token::Call { name, arity })
+                },
+                None => return Some(Err(ParseError::UnmatchedParens(self.curr_span))),
+            },
+            State::PopArgument => match self.stack.last_mut() {
+                Some(Stacked::Call { commas, .. }) => {
+                    *commas += 1;
+                    self.curr = None;
+                    self.state = State::Skip;
+                    None
+                },
                 Some(Stacked::Op(op)) => {
+                    let op = op.clone();
+                    self.stack.pop();
                     Some(tokens::Token::Oper(op))
                 },
-                None => return Some(Err(ParseError::UnmatchedParens)),
+                Some(Stacked::Unary(op)) => {
+                    let op = op.clone();
+                    self.stack.pop();
+                    Some(tokens::Token::UnaryOper(op))
+                },
+                Some(Stacked::Colon) => {
+                    self.stack.pop();
+                    Some(tokens::Token::Question)
+                },
+                Some(Stacked::Question(span)) => return Some(Err(ParseError::UnmatchedTernary(*span))),
+                Some(Stacked::LBrace(_)) | None => return Some(Err(ParseError::UnexpectedComma(self.curr_span))),
+            },
+            State::PopBranch => match self.stack.last_mut() {
+                Some(top @ Stacked::Question(_)) => {
+                    // now waits for the other branch
+                    *top = Stacked::Colon;
+                    self.curr = None;
+                    self.state = State::Skip;
+                    None
+                },
+                Some(Stacked::Op(op)) => {
+                    let op = op.clone();
+                    self.stack.pop();
+                    Some(tokens::Token::Oper(op))
+                },
+                Some(Stacked::Unary(op)) => {
+                    let op = op.clone();
+                    self.stack.pop();
+                    Some(tokens::Token::UnaryOper(op))
+                },
+                Some(Stacked::Colon) => {
+                    self.stack.pop();
+                    Some(tokens::Token::Question)
+                },
+                Some(Stacked::LBrace(_) | Stacked::Call { .. }) | None => {
+                    return Some(Err(ParseError::UnmatchedTernary(self.curr_span)))
+                },
             },
             State::PopOp => match self.stack.pop() {
                 Some(Stacked::Op(op)) => {
                     Some(tokens::Token::Oper(op))
                 },
+                Some(Stacked::Unary(op)) => Some(tokens::Token::UnaryOper(op)),
                 _ => unreachable!(),
             },
             State::Skip => { self.curr = None; None },
@@ -70,11 +187,14 @@ impl<I> Parser<I> {
             },
         };
 
-        if let Some(tokens::Token::Oper(_)) = self.curr {
+        if let Some(tokens::Token::Oper(_) | tokens::Token::Question) = self.curr {
             // will be reused on next turn, have to keep arg balance
             self.arg_balance += 1;
         }
 
+        if mb_token.is_some() {
+            self.emitted += 1;
+        }
         mb_token.map(Ok)
         // mb_token.map(|token| if self.arg_balance <= 0 {
         //     Err(ParseError::NotEnoughArgs)
@@ -82,16 +202,38 @@ impl<I> Parser<I> {
         //     Ok(token)
         // })
     }
+
+    /// Of the last token read
+    pub(crate) fn span(&self) -> tokens::Span {
+        self.curr_span
+    }
+
+    /// Ends of explicit paren groups, as counts of tokens given out, see [`crate::ast::Expr::from_infix`]
+    pub(crate) fn groups(&self) -> &[usize] {
+        &self.groups
+    }
+
+    /// Innermost paren still open, if any
+    fn unclosed_paren(&self) -> Option<tokens::Span> {
+        self.stack.iter().rev().find_map(|stacked| match stacked {
+            Stacked::LBrace(span) | Stacked::Call { span, .. } => Some(*span),
+            _ => None,
+        })
+    }
 }
 
-impl<I> Debug for Parser<I> {
+impl<I: Iterator> Debug for Parser<'_, I> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Parser")
             .field("stack", &self.stack)
             .field("curr", &self.curr)
+            .field("curr_span", &self.curr_span)
             .field("state", &self.state)
             .field("arg_balance", &self.arg_balance)
             .field("par_balance", &self.par_balance)
+            .field("grammar", &self.grammar)
+            .field("emitted", &self.emitted)
+            .field("groups", &self.groups)
             .finish()
     }
 }
@@ -99,46 +241,66 @@ impl<I> Debug for Parser<I> {
 #[derive(Debug)]
 enum Stacked {
     Op(tokens::Operation),
-    LBrace,
+    Unary(tokens::UnaryOperation),
+    LBrace(tokens::Span),
+    /// Opening paren of function call arguments, spans the name too
+    Call { name: String, commas: usize, span: tokens::Span },
+    /// `?` waiting for its `:`, nothing is popped past it until then
+    Question(tokens::Span),
+    /// `?` that got its `:`, popped as a right-associative operator
+    /// below any other
+    Colon,
 }
 
 #[derive(Debug)]
 enum State {
     PopParenLevel,
+    /// Pop operators of the current function call argument
+    PopArgument,
+    /// Pop operators of the branch before `:`
+    PopBranch,
     PopOp,
     Skip,
     CurrToOut,
 }
 
-impl<I> Iterator for Parser<I>
+impl<I> Iterator for Parser<'_, I>
 where
-    I: Iterator<Item = Result<tokens::Token, TokenizeError>>,
+    I: Iterator<Item = Result<tokens::Spanned<tokens::Token>, TokenizeError>>,
 {
     type Item = Result<tokens::Token, ParseError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
             let curr = {
-                match self.curr {
-                    Some(curr) => curr,
+                match &self.curr {
+                    Some(curr) => curr.clone(),
                     None => match self.inner.next() {
                         Some(Err(tok_err)) => return Some(Err(ParseError::Tokenization(tok_err))),
-                        Some(Ok(curr)) => {
-                            self.curr = Some(curr);
+                        Some(Ok(tokens::Spanned { value: curr, span })) => {
+                            self.curr = Some(curr.clone());
+                            self.curr_span = span;
                             curr
                         }
                         None if self.stack.is_empty() => {
                             if self.par_balance != 0 {
                                 // panic!("Unmatched parens, state: {self:?}");
-                                return Some(Err(ParseError::UnmatchedParens));
+                                return Some(Err(ParseError::UnmatchedParens(self.curr_span)));
+                            }
+                            if self.emitted > 0 && self.arg_balance != 1 {
+                                return Some(Err(ParseError::NotEnoughArgs(self.curr_span)));
                             }
                             return None
                         },
                         None => {
                             self.state = State::PopParenLevel;
-                            if self.par_balance != 0 {
+                            if let Some(span) = self.unclosed_paren() {
                                 // panic!("Non-matched parens, state: {self:?}");
-                                return Some(Err(ParseError::UnmatchedParens))
+                                return Some(Err(ParseError::UnmatchedParens(span)))
+                            }
+                            // the last operator is still waiting for its operand, like in `2 * 3 -`
+                            if self.arg_balance != 1 {
+                                return Some(Err(ParseError::NotEnoughArgs(self.curr_span)));
                             }
                             return self.token_from_state();
                         }
@@ -149,11 +311,11 @@ where
             self.visit_token(curr);
             if self.par_balance < 0 {
                 // panic!("Went under 0 paren balance: {self:?}");
-                return Some(Err(ParseError::UnmatchedParens))
+                return Some(Err(ParseError::UnmatchedParens(self.curr_span)))
             } else if self.arg_balance < 0 {
-                return Some(Err(ParseError::NotEnoughArgs))
+                return Some(Err(ParseError::NotEnoughArgs(self.curr_span)))
             } else if self.arg_balance > 1 {
-                return Some(Err(ParseError::NotEnoughOps))
+                return Some(Err(ParseError::NotEnoughOps(self.curr_span)))
             }
 
             if let Some(token) = self.token_from_state() {
@@ -163,17 +325,30 @@ where
     }
 }
 
-impl<I> TokenVisitor for Parser<I> {
+impl<I> TokenVisitor for Parser<'_, I>
+where
+    I: Iterator<Item = Result<tokens::Spanned<tokens::Token>, TokenizeError>>,
+{
     fn visit_paren(&mut self, paren: tokens::Paren) {
         match paren {
             tokens::Paren::Left => {
-                self.stack.push(Stacked::LBrace);
+                if self.arg_balance != 0 {
+                    // group right after an argument, like `2 (3)`, counts as one more,
+                    // otherwise `1 (+ x)` would take `+` for a binary one
+                    self.arg_balance += 1;
+                }
+                self.stack.push(Stacked::LBrace(self.curr_span));
                 self.state = State::Skip;
                 self.par_balance += 1;
             }
             tokens::Paren::Right => match self.state {
                 State::PopParenLevel => (),
                 _ => {
+                    let empty_call = matches!(self.stack.last(), Some(Stacked::Call { commas: 0, .. }));
+                    if self.arg_balance == 0 && !empty_call {
+                        // operator or comma right before `)`
+                        self.arg_balance -= 1;
+                    }
                     self.par_balance -= 1;
                     self.state = State::PopParenLevel;
                 },
@@ -181,25 +356,105 @@ impl<I> TokenVisitor for Parser<I> {
         }
     }
 
-    fn visit_op(&mut self, op: tokens::Operation) {
-        self.arg_balance -= 1;
-        match self.stack.last() {
-            Some(Stacked::LBrace) | None => {
-                self.stack.push(Stacked::Op(op));
-                self.state = State::Skip;
+    fn visit_comma(&mut self) {
+        match self.state {
+            State::PopArgument => (),
+            _ => {
+                self.arg_balance -= 1;
+                self.state = State::PopArgument;
             }
-            Some(Stacked::Op(stack_op)) if stack_op.prio() < op.prio() => {
-                self.stack.push(Stacked::Op(op));
-                self.state = State::Skip;
-            }
-            Some(Stacked::Op(_)) => {
-                self.state = State::PopOp;
+        }
+    }
+
+    fn visit_op(&mut self, op: tokens::Operation) {
+        if self.arg_balance == 0 {
+            // no left argument, so it may only be a prefix operator
+            if let Some(unary) = self.grammar.prefix_form(&op) {
+                return self.visit_unary(unary);
             }
         }
+
+        self.arg_balance -= 1;
+        let stack_prio = match self.stack.last() {
+            // conditional binds looser than any operator
+            Some(Stacked::LBrace(_) | Stacked::Call { .. } | Stacked::Question(_) | Stacked::Colon) | None => None,
+            Some(Stacked::Op(stack_op)) => Some(stack_op.prio()),
+            Some(Stacked::Unary(stack_op)) => Some(stack_op.prio()),
+        };
+        let should_pop = match stack_prio {
+            Some(stack_prio) if op.is_right_assoc() => stack_prio > op.prio(),
+            Some(stack_prio) => stack_prio >= op.prio(),
+            None => false,
+        };
+        if should_pop {
+            self.state = State::PopOp;
+        } else {
+            self.stack.push(Stacked::Op(op));
+            self.state = State::Skip;
+        }
+    }
+
+    fn visit_unary(&mut self, op: tokens::UnaryOperation) {
+        if self.arg_balance != 0 {
+            // prefix-only one right after an argument, like `2 !`, counts as one more
+            self.arg_balance += 1;
+        }
+        // has no left argument, so there is nothing to pop before it
+        self.stack.push(Stacked::Unary(op));
+        self.state = State::Skip;
     }
 
     fn visit_num(&mut self, _num: tokens::Number) {
         self.arg_balance += 1;
         self.state = State::CurrToOut;
     }
+
+    fn visit_ident(&mut self, name: String) {
+        if let Some(Ok(tokens::Spanned { value: tokens::Token::Paren(tokens::Paren::Left), span })) = self.inner.peek() {
+            let span = self.curr_span.join(*span);
+            self.inner.next();
+            if self.arg_balance != 0 {
+                // call right after an argument, like `2 f(3)`, counts as one more
+                self.arg_balance += 1;
+            }
+            self.stack.push(Stacked::Call { name, commas: 0, span });
+            self.par_balance += 1;
+            self.state = State::Skip;
+        } else {
+            self.arg_balance += 1;
+            self.state = State::CurrToOut;
+        }
+    }
+
+    fn visit_call(&mut self, _name: String, arity: usize) {
+        // already in postfix form, consumes its arguments and becomes one
+        let balance = (i64::from(self.arg_balance) + 1).saturating_sub(i64::try_from(arity).unwrap_or(i64::MAX));
+        // anything below zero is reported the same
+        self.arg_balance = i8::try_from(balance).unwrap_or(i8::MIN);
+        self.state = State::CurrToOut;
+    }
+
+    fn visit_question(&mut self) {
+        self.arg_balance -= 1;
+        match self.stack.last() {
+            // the condition is over
+            Some(Stacked::Op(_) | Stacked::Unary(_)) => self.state = State::PopOp,
+            // right-associative: `a ? b : c ? d : e` is `a ? b : (c ? d : e)`
+            _ => {
+                self.stack.push(Stacked::Question(self.curr_span));
+                self.state = State::Skip;
+            }
+        }
+    }
+
+    fn visit_colon(&mut self) {
+        match self.state {
+            State::PopBranch => (),
+            _ => {
+                // owes the other branch, so `a ? b :` is short of an argument at the end
+                self.arg_balance -= 1;
+                self.state = State::PopBranch;
+            }
+        }
+    }
 }
@@ -0,0 +1,357 @@
+use crate::{
+    parser::{ParseError, Parser},
+    tokenizer::{approximate, tokenize, TokenizeErrorKind},
+    tokens::{Number, Paren, Span, Spanned, Token},
+};
+
+/// Outcome of [`parse_recovering`]
+#[derive(Debug, PartialEq)]
+pub struct Recovered {
+    /// Postfix form of the input with the broken parts dropped
+    pub tokens: Vec<Token>,
+    /// Every error found, in order of appearance
+    pub errors: Vec<ParseError>,
+}
+
+/// Parses `input` without stopping at the first error: invalid symbols are skipped,
+/// dangling operators and commas are dropped, as well as operands without an operator
+/// before them and branches of `?` without `:`, and unclosed parens get closed at the end
+pub fn parse_recovering(input: &str) -> Recovered {
+    let mut errors = vec![];
+    let mut infix = vec![];
+    let mut tokenizer = tokenize(input);
+    while let Some(tok) = tokenizer.next() {
+        match tok {
+            Ok(tok) => infix.push(tok),
+            Err(err) => {
+                if *err.kind() == TokenizeErrorKind::NumberTooLarge {
+                    // rough value instead, so that the operand is not missing
+                    let span = Span::new(err.at(), tokenizer.consumed());
+                    let approx = approximate(&input[span.start..span.end]);
+                    infix.push(Spanned::new(Token::Number(Number::Float(approx)), span));
+                }
+                errors.push(ParseError::Tokenization(err));
+            }
+        }
+    }
+
+    let mut repair = Repair { expect_operand: true, ..Repair::default() };
+    let mut toks = infix.into_iter().peekable();
+    while let Some(Spanned { value, span }) = toks.next() {
+        let call_paren = match (&value, toks.peek()) {
+            (Token::Ident(_), Some(Spanned { value: Token::Paren(Paren::Left), .. })) => toks.next(),
+            _ => None,
+        };
+        repair.step(Spanned::new(value, span), call_paren);
+    }
+    repair.finish(input.len());
+    errors.append(&mut repair.errors);
+    errors.sort_by_key(|err| err.span().map(|span| span.start));
+
+    // repaired input is valid, the parser can't fail on it
+    match Parser::new(repair.out.into_iter().map(Ok)).collect() {
+        Ok(tokens) => Recovered { tokens, errors },
+        Err(err) => {
+            errors.push(err);
+            Recovered { tokens: vec![], errors }
+        }
+    }
+}
+
+/// Infix tokens fixed on the fly, so that they always make a valid expression
+#[derive(Debug, Default)]
+struct Repair {
+    out: Vec<Spanned<Token>>,
+    errors: Vec<ParseError>,
+    /// Open parens, `true` for call ones
+    opened: Vec<(bool, Span)>,
+    expect_operand: bool,
+    /// Depth of the dropped parens group, with its opening paren
+    skipped: Option<(usize, Span)>,
+    /// `?` waiting for `:`: index in `out`, depth of parens and span
+    questions: Vec<(usize, usize, Span)>,
+    /// Index in `out` of the `?` of each `:` there
+    colons: Vec<usize>,
+}
+
+impl Repair {
+    fn step(&mut self, tok: Spanned<Token>, call_paren: Option<Spanned<Token>>) {
+        if let Some((depth, _)) = &mut self.skipped {
+            match tok.value {
+                Token::Paren(Paren::Left) => *depth += 1,
+                Token::Paren(Paren::Right) => *depth -= 1,
+                _ if call_paren.is_some() => *depth += 1,
+                _ => (),
+            }
+            if *depth == 0 {
+                self.skipped = None;
+            }
+            return;
+        }
+
+        let Spanned { value, span } = tok;
+        match value {
+            Token::Number(_) | Token::Ident(_) | Token::Paren(Paren::Left) if !self.expect_operand => {
+                self.errors.push(ParseError::NotEnoughOps(span));
+                let opens = call_paren.is_some() || value == Token::Paren(Paren::Left);
+                if opens {
+                    self.skipped = Some((1, span));
+                }
+            }
+            Token::Ident(_) if call_paren.is_some() => {
+                let paren = call_paren.unwrap();
+                self.opened.push((true, span.join(paren.span)));
+                self.out.push(Spanned::new(value, span));
+                self.out.push(paren);
+            }
+            Token::Number(_) | Token::Ident(_) => {
+                self.out.push(Spanned::new(value, span));
+                self.expect_operand = false;
+            }
+            Token::Paren(Paren::Left) => {
+                self.opened.push((false, span));
+                self.out.push(Spanned::new(value, span));
+            }
+            Token::Paren(Paren::Right) => {
+                let depth = self.opened.len();
+                if depth == 0 {
+                    self.errors.push(ParseError::UnmatchedParens(span));
+                    return;
+                }
+                let empty_call = matches!(self.opened.last(), Some((true, _)))
+                    && matches!(self.out.last(), Some(Spanned { value: Token::Paren(Paren::Left), .. }));
+                if self.expect_operand && !empty_call {
+                    self.errors.push(ParseError::NotEnoughArgs(span));
+                    self.drop_dangling();
+                    if self.opened.len() < depth {
+                        // its paren is gone too
+                        return;
+                    }
+                }
+                self.close_ternaries();
+                self.opened.pop();
+                self.out.push(Spanned::new(value, span));
+                self.expect_operand = false;
+            }
+            Token::Comma => match self.opened.last() {
+                Some((true, _)) if self.expect_operand => self.errors.push(ParseError::NotEnoughArgs(span)),
+                Some((true, _)) => {
+                    self.close_ternaries();
+                    self.out.push(Spanned::new(value, span));
+                    self.expect_operand = true;
+                }
+                _ => self.errors.push(ParseError::UnexpectedComma(span)),
+            },
+            Token::Oper(ref op) if self.expect_operand => {
+                if crate::tokens::UnaryOperation::from_binary(op).is_some() {
+                    self.out.push(Spanned::new(value, span));
+                } else {
+                    self.errors.push(ParseError::NotEnoughArgs(span));
+                }
+            }
+            Token::Oper(_) => {
+                self.out.push(Spanned::new(value, span));
+                self.expect_operand = true;
+            }
+            Token::UnaryOper(_) if self.expect_operand => self.out.push(Spanned::new(value, span)),
+            Token::UnaryOper(_) => self.errors.push(ParseError::NotEnoughOps(span)),
+            Token::Question if self.expect_operand => self.errors.push(ParseError::NotEnoughArgs(span)),
+            Token::Question => {
+                self.questions.push((self.out.len(), self.opened.len(), span));
+                self.out.push(Spanned::new(value, span));
+                self.expect_operand = true;
+            }
+            Token::Colon => {
+                let dangling = self.expect_operand;
+                if dangling {
+                    self.errors.push(ParseError::NotEnoughArgs(span));
+                    self.drop_dangling();
+                }
+                match self.questions.last() {
+                    Some(&(at, depth, _)) if depth == self.opened.len() && !self.expect_operand => {
+                        self.questions.pop();
+                        self.colons.push(at);
+                        self.out.push(Spanned::new(value, span));
+                        self.expect_operand = true;
+                    }
+                    _ if dangling => (),
+                    _ => self.errors.push(ParseError::UnmatchedTernary(span)),
+                }
+            }
+            Token::Call { .. } => unreachable!("not produced by the tokenizer"),
+        }
+    }
+
+    /// Drops trailing operators, commas and parens until the last token is an operand
+    fn drop_dangling(&mut self) {
+        while self.expect_operand {
+            match self.out.last().map(|tok| &tok.value) {
+                Some(Token::Oper(_) | Token::UnaryOper(_) | Token::Comma) => {
+                    self.out.pop();
+                }
+                Some(Token::Question) => {
+                    self.out.pop();
+                    self.questions.pop();
+                }
+                Some(Token::Colon) => {
+                    // the first branch can't stand without the second one
+                    let at = self.colons.pop().unwrap();
+                    self.out.truncate(at);
+                    self.colons.retain(|&question| question < at);
+                }
+                Some(Token::Paren(Paren::Left)) => {
+                    self.out.pop();
+                    if let Some((true, _)) = self.opened.pop() {
+                        self.out.pop();
+                    }
+                }
+                Some(_) => self.expect_operand = false,
+                None => break,
+            }
+        }
+    }
+
+    fn finish(&mut self, end: usize) {
+        if let Some((_, span)) = self.skipped.take() {
+            self.errors.push(ParseError::UnmatchedParens(span));
+        }
+        if self.expect_operand {
+            if let Some(last) = self.out.last() {
+                self.errors.push(ParseError::NotEnoughArgs(last.span));
+            }
+            self.drop_dangling();
+        }
+        self.close_ternaries();
+        while let Some((_, span)) = self.opened.pop() {
+            self.errors.push(ParseError::UnmatchedParens(span));
+            self.out.push(Spanned::new(Token::Paren(Paren::Right), Span::new(end, end)));
+            self.close_ternaries();
+        }
+    }
+
+    /// Drops each `?` of the current paren level that has no `:`, with its branch
+    fn close_ternaries(&mut self) {
+        while let Some(&(at, depth, span)) = self.questions.last() {
+            if depth != self.opened.len() {
+                break;
+            }
+            self.questions.pop();
+            self.errors.push(ParseError::UnmatchedTernary(span));
+            self.out.truncate(at);
+            self.colons.retain(|&question| question < at);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parser::ParseError,
+        tokenizer::TokenizeError,
+        tokens::Span,
+        Printer,
+    };
+
+    use super::parse_recovering;
+
+    fn recover(input: &str) -> (String, Vec<ParseError>) {
+        let res = parse_recovering(input);
+        (Printer(&res.tokens).to_string(), res.errors)
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(recover("max(1, 2) * (3 - x)"), ("1 2 max/2 3 x - *".to_owned(), vec![]));
+        assert_eq!(recover("f()"), ("f/0".to_owned(), vec![]));
+        assert_eq!(recover("!a || not b"), ("a ! b ! ||".to_owned(), vec![]));
+        assert_eq!(recover("a ? b ? 1 : 2 : c"), ("a b 1 2 ? c ?".to_owned(), vec![]));
+    }
+
+    #[test]
+    fn all_errors() {
+        assert_eq!(
+            recover("1 # 2 + * 3 )"),
+            (
+                "1 3 +".to_owned(),
+                vec![
+                    ParseError::Tokenization(TokenizeError::invalid_symbol(2)),
+                    ParseError::NotEnoughOps(Span::new(4, 5)),
+                    ParseError::NotEnoughArgs(Span::new(8, 9)),
+                    ParseError::UnmatchedParens(Span::new(12, 13)),
+                ]
+            )
+        );
+        assert_eq!(
+            recover("max(1,) + (2 *) , 3"),
+            (
+                "1 max/1 2 +".to_owned(),
+                vec![
+                    ParseError::NotEnoughArgs(Span::new(6, 7)),
+                    ParseError::NotEnoughArgs(Span::new(14, 15)),
+                    ParseError::UnexpectedComma(Span::new(16, 17)),
+                    ParseError::NotEnoughOps(Span::new(18, 19)),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn partial() {
+        assert_eq!(
+            recover("(1 + 2 (3) * 4"),
+            (
+                "1 2 4 * +".to_owned(),
+                vec![ParseError::UnmatchedParens(Span::new(0, 1)), ParseError::NotEnoughOps(Span::new(7, 8))]
+            )
+        );
+        assert_eq!(
+            recover("2 * (3 +"),
+            (
+                "2 3 *".to_owned(),
+                vec![ParseError::UnmatchedParens(Span::new(4, 5)), ParseError::NotEnoughArgs(Span::new(7, 8))]
+            )
+        );
+        assert_eq!(recover("()").0, "");
+    }
+
+    #[test]
+    fn conditional() {
+        assert_eq!(
+            recover("max(a ? b, c ? 1 : ) + (x ? y"),
+            (
+                "a c max/2 x +".to_owned(),
+                vec![
+                    ParseError::UnmatchedTernary(Span::new(6, 7)),
+                    ParseError::NotEnoughArgs(Span::new(19, 20)),
+                    ParseError::UnmatchedParens(Span::new(23, 24)),
+                    ParseError::UnmatchedTernary(Span::new(26, 27)),
+                ]
+            )
+        );
+        assert_eq!(
+            recover("a : b ? : c"),
+            (
+                "a".to_owned(),
+                vec![
+                    ParseError::UnmatchedTernary(Span::new(2, 3)),
+                    ParseError::NotEnoughOps(Span::new(4, 5)),
+                    ParseError::NotEnoughArgs(Span::new(8, 9)),
+                    ParseError::NotEnoughOps(Span::new(10, 11)),
+                ]
+            )
+        );
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn large_number() {
+        use crate::tokens::{Number, Operation, Token};
+
+        let res = parse_recovering("1 + 99999999999999999999");
+        assert_eq!(res.errors, vec![ParseError::Tokenization(TokenizeError::number_too_large(4))]);
+        assert_eq!(
+            res.tokens,
+            vec![Token::Number(Number::Int(1)), Token::Number(Number::Float(1e20)), Token::Oper(Operation::Add)]
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use expr_parser::{
+    ast::Expr,
+    diagnostics, parser,
+    tokenizer::tokenize,
+    tokens::{Paren, Token},
+    Printer,
+};
+use rustyline::{error::ReadlineError, DefaultEditor};
+
+use super::Format;
+
+const HELP: &str = "\
+Enter an expression to evaluate it, lines are joined while parens are unclosed.
+Commands:
+  :rpn EXPR     show postfix form built by the parser
+  :tokens EXPR  show tokens with their byte spans
+  :ast EXPR     show syntax tree
+  :help         show this message
+  :quit         exit, as does Ctrl-D";
+
+/// Interactive session with line editing, history is kept in `~/.expr_parser_history`
+pub fn run(format: Format) -> rustyline::Result<()> {
+    let mut editor = DefaultEditor::new()?;
+    let history = history_path();
+    if let Some(path) = &history {
+        // there is no history on the first run
+        let _ = editor.load_history(path);
+    }
+
+    let mut pending = Pending::default();
+    loop {
+        let prompt = if pending.is_empty() { "> " } else { ". " };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                let Some(entry) = pending.push(&line) else {
+                    continue;
+                };
+                if entry.trim().is_empty() {
+                    continue;
+                }
+                editor.add_history_entry(entry.as_str())?;
+                if !command(entry.trim(), format, &mut io::stdout().lock(), &mut io::stderr().lock())? {
+                    break;
+                }
+            }
+            // drops what was typed so far, like a shell
+            Err(ReadlineError::Interrupted) => pending = Pending::default(),
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err),
+        }
+    }
+
+    if let Some(path) = &history {
+        editor.save_history(path)?;
+    }
+    Ok(())
+}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".expr_parser_history"))
+}
+
+/// Lines of an entry, joined while parens are unclosed
+#[derive(Debug, Default)]
+struct Pending(String);
+
+impl Pending {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Adds a line, gives the whole entry back once its parens are closed
+    fn push(&mut self, line: &str) -> Option<String> {
+        if !self.0.is_empty() {
+            self.0.push('\n');
+        }
+        self.0.push_str(line);
+        if open_parens(&self.0) > 0 {
+            return None;
+        }
+        Some(std::mem::take(&mut self.0))
+    }
+}
+
+/// Parens still waiting to be closed, invalid symbols don't matter here
+fn open_parens(input: &str) -> i32 {
+    tokenize(input)
+        .filter_map(Result::ok)
+        .map(|tok| match tok.value {
+            Token::Paren(Paren::Left) => 1,
+            Token::Paren(Paren::Right) => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Runs a meta-command or evaluates the input, writing to `out` and `err_out`, `false` means exit
+fn command(input: &str, format: Format, out: &mut impl Write, err_out: &mut impl Write) -> io::Result<bool> {
+    let Some(command) = input.strip_prefix(':') else {
+        super::evaluate(input, format, out, err_out)?;
+        return Ok(true);
+    };
+    let (name, expr) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+    let expr = expr.trim();
+    match name {
+        "rpn" => match parser::parse(expr).collect::<Result<Vec<_>, _>>() {
+            Ok(tokens) => writeln!(out, "{}", Printer(&tokens))?,
+            Err(err) => write!(err_out, "{}", diagnostics::render_parse_error(expr, &err))?,
+        },
+        "tokens" => {
+            for tok in tokenize(expr) {
+                match tok {
+                    Ok(tok) => writeln!(out, "{:>8}  {}", format!("{}..{}", tok.span.start, tok.span.end), tok.value)?,
+                    Err(err) => write!(err_out, "{}", diagnostics::render_tokenize_error(expr, &err))?,
+                }
+            }
+        }
+        "ast" => match Expr::parse(expr) {
+            Ok(expr) => writeln!(out, "{expr:#?}")?,
+            Err(err) => write!(err_out, "{}", diagnostics::render_parse_error(expr, &err))?,
+        },
+        "help" => writeln!(out, "{HELP}")?,
+        "quit" | "q" => return Ok(false),
+        _ => writeln!(err_out, "Unknown command `:{name}`, see `:help`")?,
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use expr_parser::{diagnostics, parser::ParseError, tokens::Span};
+
+    use super::{command, open_parens, Pending, HELP};
+    use crate::{Format, Input, Radix};
+
+    fn run(input: &str) -> (bool, String, String) {
+        let format = Format { input: Input::Infix, output: None, radix: Radix::Dec };
+        let (mut out, mut err_out) = (Vec::new(), Vec::new());
+        let go_on = command(input, format, &mut out, &mut err_out).unwrap();
+        (go_on, String::from_utf8(out).unwrap(), String::from_utf8(err_out).unwrap())
+    }
+
+    #[test]
+    fn continuation() {
+        assert_eq!(open_parens("((1)"), 1);
+        assert_eq!(open_parens("1 # ("), 1);
+        assert_eq!(open_parens(")("), 0);
+
+        let mut pending = Pending::default();
+        assert_eq!(pending.push("max(1,"), None);
+        assert!(!pending.is_empty());
+        assert_eq!(pending.push("(2"), None);
+        assert_eq!(pending.push("))"), Some("max(1,\n(2\n))".to_owned()));
+        assert!(pending.is_empty());
+        // an extra closing paren is the parser's business
+        assert_eq!(pending.push("1)"), Some("1)".to_owned()));
+        assert_eq!(pending.push(""), Some(String::new()));
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(run("1 + 2"), (true, "1 2 + = 3\n".to_owned(), String::new()));
+        assert_eq!(run(":rpn 1 + 2 * 3"), (true, "1 2 3 * +\n".to_owned(), String::new()));
+        assert_eq!(run(":tokens 1+"), (true, "    0..1  1\n    1..2  +\n".to_owned(), String::new()));
+        assert_eq!(run(":help"), (true, format!("{HELP}\n"), String::new()));
+        assert_eq!(run(":quit"), (false, String::new(), String::new()));
+        assert_eq!(run(":q"), (false, String::new(), String::new()));
+        assert_eq!(run(":nope 1"), (true, String::new(), "Unknown command `:nope`, see `:help`\n".to_owned()));
+
+        let (go_on, out, err_out) = run(":ast  12 + 34 -");
+        assert!(go_on);
+        assert_eq!(out, "");
+        let err = ParseError::NotEnoughArgs(Span::new(8, 9));
+        assert_eq!(err_out, diagnostics::render_parse_error("12 + 34 -", &err));
+        assert!(run(":ast 1 + x").1.starts_with("Binary"), "{:?}", run(":ast 1 + x"));
+    }
+}
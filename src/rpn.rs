@@ -0,0 +1,191 @@
+use std::iter::Peekable;
+
+use crate::{
+    parser::ParseError,
+    tokenizer::{tokenize, TokenizeError, Tokenizer},
+    tokens::{self, Span, Spanned, Token},
+};
+
+/// Reads postfix text, as written by [`crate::Printer`], into the same tokens [`crate::parser::Parser`] yields
+pub struct RpnParser<I: Iterator> {
+    inner: Glued<I>,
+    /// Operands on the stack so far
+    depth: usize,
+    last_span: Span,
+    done: bool,
+}
+
+pub fn parse_rpn(input: &str) -> RpnParser<Tokenizer<'_>> {
+    RpnParser::new(tokenize(input))
+}
+
+impl<I> RpnParser<I>
+where
+    I: Iterator<Item = Result<Spanned<Token>, TokenizeError>>,
+{
+    pub fn new(inner: I) -> Self {
+        Self { inner: Glued::new(inner), depth: 0, last_span: Span::default(), done: false }
+    }
+}
+
+/// Tokens of the notations written with [`crate::Printer`]: glues `u-` into
+/// unary operators and `name/arity` into calls
+pub(crate) struct Glued<I: Iterator> {
+    inner: Peekable<I>,
+    /// Token taken while looking for a call, goes next
+    pending: Option<Spanned<Token>>,
+}
+
+impl<I> Glued<I>
+where
+    I: Iterator<Item = Result<Spanned<Token>, TokenizeError>>,
+{
+    pub(crate) fn new(inner: I) -> Self {
+        Self { inner: inner.peekable(), pending: None }
+    }
+
+    /// Takes the next token if it goes right after `span` without spaces
+    fn next_adjacent(&mut self, span: Span, pred: impl FnOnce(&Token) -> bool) -> Option<Spanned<Token>> {
+        match self.inner.peek() {
+            Some(Ok(tok)) if tok.span.start == span.end && pred(&tok.value) => self.inner.next()?.ok(),
+            _ => None,
+        }
+    }
+
+    fn glue(&mut self, tok: Spanned<Token>) -> Spanned<Token> {
+        let Spanned { value: Token::Ident(name), span } = tok else {
+            return tok;
+        };
+
+        let is_unary = |tok: &Token| matches!(tok, Token::Oper(op) if tokens::UnaryOperation::from_binary(op).is_some());
+        if name == "u" {
+            if let Some(Spanned { value: Token::Oper(op), span: op_span }) = self.next_adjacent(span, is_unary) {
+                let op = tokens::UnaryOperation::from_binary(&op).unwrap();
+                return Spanned::new(Token::UnaryOper(op), span.join(op_span));
+            }
+        }
+
+        let Some(slash) = self.next_adjacent(span, |tok| *tok == Token::Oper(tokens::Operation::Div)) else {
+            return Spanned::new(Token::Ident(name), span);
+        };
+        let is_arity = |tok: &Token| matches!(tok, Token::Number(tokens::Number::Int(_)));
+        match self.next_adjacent(slash.span, is_arity) {
+            Some(Spanned { value: Token::Number(tokens::Number::Int(arity)), span: arity_span }) => {
+                let arity = arity.to_string().parse().unwrap_or(usize::MAX);
+                Spanned::new(Token::Call { name, arity }, span.join(arity_span))
+            }
+            _ => {
+                self.pending = Some(slash);
+                Spanned::new(Token::Ident(name), span)
+            }
+        }
+    }
+}
+
+impl<I> Iterator for Glued<I>
+where
+    I: Iterator<Item = Result<Spanned<Token>, TokenizeError>>,
+{
+    type Item = Result<Spanned<Token>, TokenizeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.pending.take() {
+            Some(tok) => Some(Ok(tok)),
+            None => Some(self.inner.next()?.map(|tok| self.glue(tok))),
+        }
+    }
+}
+
+impl<I> Iterator for RpnParser<I>
+where
+    I: Iterator<Item = Result<Spanned<Token>, TokenizeError>>,
+{
+    type Item = Result<Token, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let tok = match self.inner.next() {
+            Some(Ok(tok)) => tok,
+            Some(Err(tok_err)) => {
+                self.done = true;
+                return Some(Err(ParseError::Tokenization(tok_err)));
+            }
+            None => {
+                self.done = true;
+                // every operand but the result has to be consumed
+                return (self.depth > 1).then_some(Err(ParseError::NotEnoughOps(self.last_span)));
+            }
+        };
+
+        self.last_span = tok.span;
+        let args = match &tok.value {
+            Token::Number(_) | Token::Ident(_) => 0,
+            Token::UnaryOper(_) => 1,
+            Token::Oper(_) => 2,
+            Token::Question => 3,
+            Token::Call { arity, .. } => *arity,
+            Token::Comma => {
+                self.done = true;
+                return Some(Err(ParseError::UnexpectedComma(tok.span)));
+            }
+            // grouping is meaningless in postfix, `?` alone makes the conditional
+            Token::Paren(_) => {
+                self.done = true;
+                return Some(Err(ParseError::UnmatchedParens(tok.span)));
+            }
+            Token::Colon => {
+                self.done = true;
+                return Some(Err(ParseError::UnmatchedTernary(tok.span)));
+            }
+        };
+        if self.depth < args {
+            self.done = true;
+            return Some(Err(ParseError::NotEnoughArgs(tok.span)));
+        }
+        self.depth = self.depth - args + 1;
+        Some(Ok(tok.value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        calculate,
+        parser::{parse, ParseError},
+        tokenizer::TokenizeError,
+        tokens::Span,
+        Printer,
+    };
+
+    use super::parse_rpn;
+
+    #[test]
+    fn round_trip() {
+        for input in ["1 + 2 * 3", "-(2 ** 3) // +x", "max(1, 2.5, f()) % 7", "u - 1", "min(a / 2)", "a ? b : -c"] {
+            let tokens = parse(input).collect::<Result<Vec<_>, _>>().unwrap();
+            let text = Printer(&tokens).to_string();
+            assert_eq!(parse_rpn(&text).collect::<Result<Vec<_>, _>>(), Ok(tokens), "{text}");
+        }
+        assert_eq!(calculate(parse_rpn("2 3 4 * + u-").map(Result::unwrap)).to_string(), "-14");
+        assert_eq!(calculate(parse_rpn("1 2+3*").map(Result::unwrap)).to_string(), "9");
+    }
+
+    #[test]
+    fn failures() {
+        for (input, err) in [
+            ("1 +", ParseError::NotEnoughArgs(Span::new(2, 3))),
+            ("1 2 max/3", ParseError::NotEnoughArgs(Span::new(4, 9))),
+            ("u-", ParseError::NotEnoughArgs(Span::new(0, 2))),
+            ("1 2 3 +", ParseError::NotEnoughOps(Span::new(6, 7))),
+            ("1 , 2", ParseError::UnexpectedComma(Span::new(2, 3))),
+            ("( 1 )", ParseError::UnmatchedParens(Span::new(0, 1))),
+            ("1 2 3 ? : +", ParseError::UnmatchedTernary(Span::new(8, 9))),
+            ("1 # +", ParseError::Tokenization(TokenizeError::invalid_symbol(2))),
+        ] {
+            assert_eq!(parse_rpn(input).collect::<Result<Vec<_>, _>>(), Err(err), "{input}");
+        }
+        assert_eq!(parse_rpn("").count(), 0);
+    }
+}
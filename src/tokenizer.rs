@@ -1,29 +1,45 @@
 use std::fmt::Display;
 
-use crate::tokens;
+use crate::{grammar::{self, Grammar}, tokens};
 
 #[derive(Debug)]
 pub struct Tokenizer<'s> {
     input: &'s str,
+    /// Bytes of the original input already consumed
+    offset: usize,
     state: State,
+    grammar: &'s Grammar,
 }
 
-pub fn tokenize(input: &str) -> Tokenizer {
+/// After an error the offending symbol or literal is skipped, so going on
+/// with the iteration yields the following tokens and errors
+pub fn tokenize(input: &str) -> Tokenizer<'_> {
     Tokenizer {
         input,
+        offset: 0,
         state: State::General(GeneralState {}),
+        grammar: grammar::builtin(),
     }
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum TokenizeErrorKind {
     InvalidSymbol,
+    /// Literal doesn't fit into [`tokens::Int`]
+    NumberTooLarge,
+    /// Digit the base of the literal doesn't have, like `2` in `0b102`
+    InvalidDigit,
+    /// Unknown base prefix, prefix without digits or `_` not between digits
+    MalformedNumber,
 }
 
 impl Display for TokenizeErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             TokenizeErrorKind::InvalidSymbol => f.write_str("invalid symbol"),
+            TokenizeErrorKind::NumberTooLarge => f.write_str("number is too large"),
+            TokenizeErrorKind::InvalidDigit => f.write_str("invalid digit for the base of the number"),
+            TokenizeErrorKind::MalformedNumber => f.write_str("malformed number"),
         }
     }
 }
@@ -31,13 +47,34 @@ impl Display for TokenizeErrorKind {
 #[derive(Debug, PartialEq, Eq)]
 pub struct TokenizeError {
     kind: TokenizeErrorKind,
+    /// Byte offset in the original input
     at: usize,
 }
 
 impl TokenizeError {
+    pub fn kind(&self) -> &TokenizeErrorKind {
+        &self.kind
+    }
+
+    pub fn at(&self) -> usize {
+        self.at
+    }
+
     pub fn invalid_symbol(at: usize) -> Self {
         Self { kind: TokenizeErrorKind::InvalidSymbol, at }
     }
+
+    pub fn number_too_large(at: usize) -> Self {
+        Self { kind: TokenizeErrorKind::NumberTooLarge, at }
+    }
+
+    pub fn invalid_digit(at: usize) -> Self {
+        Self { kind: TokenizeErrorKind::InvalidDigit, at }
+    }
+
+    pub fn malformed_number(at: usize) -> Self {
+        Self { kind: TokenizeErrorKind::MalformedNumber, at }
+    }
 }
 
 impl Display for TokenizeError {
@@ -48,18 +85,36 @@ impl Display for TokenizeError {
 
 impl std::error::Error for TokenizeError {}
 
+impl<'s> Tokenizer<'s> {
+    /// Recognises the operators of `grammar` too, they win over builtin ones
+    /// with the same symbol
+    pub fn with_grammar(self, grammar: &'s Grammar) -> Self {
+        Self { grammar, ..self }
+    }
+
+    /// Bytes of the original input already consumed
+    pub(crate) fn consumed(&self) -> usize {
+        self.offset
+    }
+}
+
 impl Iterator for Tokenizer<'_> {
-    type Item = Result<tokens::Token, TokenizeError>;
+    type Item = Result<tokens::Spanned<tokens::Token>, TokenizeError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let (outcome, to_skip) = self.state.process(self.input);
+            let (outcome, to_skip) = self.state.process(self.input, self.grammar);
+            let start = self.offset;
             self.input = &self.input[to_skip..];
+            self.offset += to_skip;
             match outcome {
-                Outcome::Token(tok) => break Some(Ok(tok)),
+                Outcome::Token(tok, span) => break Some(Ok(tokens::Spanned::new(tok, span.shift(start)))),
                 Outcome::State(ns) => self.state = ns,
                 Outcome::Done => break None,
-                Outcome::Error(err) => break Some(Err(err)),
+                Outcome::Error(mut err) => {
+                    err.at += start;
+                    break Some(Err(err));
+                }
             }
         }
     }
@@ -68,58 +123,230 @@ impl Iterator for Tokenizer<'_> {
 #[derive(Debug, Clone, Copy)]
 enum State {
     Number(NumberState),
+    Ident(IdentState),
     General(GeneralState),
 }
 
 #[derive(Debug)]
 enum Outcome {
-    Token(tokens::Token),
+    /// Token and its span relative to the processed string
+    Token(tokens::Token, tokens::Span),
     State(State),
     Done,
     Error(TokenizeError),
 }
 
-fn try_parse_oper(part: char) -> Option<tokens::Operation> {
-    match part {
-        '+' => Some(tokens::Operation::Add),
-        '-' => Some(tokens::Operation::Sub),
-        '*' => Some(tokens::Operation::Mul),
-        '/' => Some(tokens::Operation::Div),
-        _ => None,
+/// Builtin binary operators, longer symbols first, so the first match is the longest one
+const OPERS: [(&str, tokens::Operation); 26] = {
+    use tokens::{Bitwise, Comparison, Logic};
+
+    [
+        ("**", tokens::Operation::Pow),
+        ("//", tokens::Operation::FloorDiv),
+        ("<<", tokens::Operation::Bit(Bitwise::Shl)),
+        (">>", tokens::Operation::Bit(Bitwise::Shr)),
+        ("==", tokens::Operation::Cmp(Comparison::Eq)),
+        ("!=", tokens::Operation::Cmp(Comparison::Ne)),
+        ("<=", tokens::Operation::Cmp(Comparison::Le)),
+        (">=", tokens::Operation::Cmp(Comparison::Ge)),
+        ("<", tokens::Operation::Cmp(Comparison::Lt)),
+        (">", tokens::Operation::Cmp(Comparison::Gt)),
+        ("&&", tokens::Operation::Logic(Logic::And)),
+        ("||", tokens::Operation::Logic(Logic::Or)),
+        ("&", tokens::Operation::Bit(Bitwise::And)),
+        ("|", tokens::Operation::Bit(Bitwise::Or)),
+        ("+", tokens::Operation::Add),
+        ("-", tokens::Operation::Sub),
+        ("*", tokens::Operation::Mul),
+        ("/", tokens::Operation::Div),
+        ("%", tokens::Operation::Rem),
+        ("^", tokens::Operation::Pow),
+        // typographic forms, as pasted from documents
+        ("−", tokens::Operation::Sub),
+        ("×", tokens::Operation::Mul),
+        ("÷", tokens::Operation::Div),
+        ("≠", tokens::Operation::Cmp(Comparison::Ne)),
+        ("≤", tokens::Operation::Cmp(Comparison::Le)),
+        ("≥", tokens::Operation::Cmp(Comparison::Ge)),
+    ]
+};
+
+/// Builtin binary operator spelled exactly as `symbol`, words included
+pub(crate) fn builtin_oper(symbol: &str) -> Option<tokens::Operation> {
+    match symbol {
+        "and" => Some(tokens::Operation::Logic(tokens::Logic::And)),
+        "or" => Some(tokens::Operation::Logic(tokens::Logic::Or)),
+        "xor" => Some(tokens::Operation::Bit(tokens::Bitwise::Xor)),
+        _ => OPERS.into_iter().find(|(sym, _)| *sym == symbol).map(|(_, op)| op),
+    }
+}
+
+/// Operator at the start of `part` and its length, longer symbols take precedence,
+/// ones of the grammar win over builtin ones of the same length
+fn try_parse_oper(part: &str, grammar: &Grammar) -> Option<(tokens::Token, usize)> {
+    let builtin = OPERS
+        .into_iter()
+        .find(|(sym, _)| part.starts_with(sym))
+        .map(|(sym, op)| (tokens::Token::Oper(op), sym.len()));
+    match grammar.longest_symbol(part) {
+        Some(sym) if builtin.as_ref().is_none_or(|(_, len)| sym.len() >= *len) => {
+            grammar.token(sym).map(|tok| (tok, sym.len()))
+        }
+        _ => builtin,
     }
 }
 
-fn is_oper(part: char) -> bool {
-    try_parse_oper(part).is_some()
+fn is_oper(part: &str, grammar: &Grammar) -> bool {
+    try_parse_oper(part, grammar).is_some()
+}
+
+fn starts_with_digit(s: &str) -> bool {
+    s.chars().next().is_some_and(|ch| ch.is_ascii_digit())
+}
+
+pub(crate) fn is_ident_start(ch: char) -> bool {
+    ch.is_alphabetic() || ch == '_'
+}
+
+pub(crate) fn is_ident_continue(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
 }
 
 #[derive(Debug, Default, Clone, Copy)]
 struct NumberState {}
 
 trait ParseStep {
-    fn process(&mut self, s: &str) -> (Outcome, usize);
+    fn process(&mut self, s: &str, grammar: &Grammar) -> (Outcome, usize);
 }
 
 impl ParseStep for NumberState {
-    fn process(&mut self, s: &str) -> (Outcome, usize) {
-        let mut chars = s.chars();
-        let mut to_skip = 0;
-        while let Some(ch) = chars.next() {
-            if !ch.is_digit(10) {
-                break;
+    fn process(&mut self, s: &str, _grammar: &Grammar) -> (Outcome, usize) {
+        let bytes = s.as_bytes();
+        // a letter after the leading zero is a base, except for the exponent of `0e5`
+        let is_base = |b: &u8| b.is_ascii_alphabetic() && !b.eq_ignore_ascii_case(&b'e');
+        if bytes.first() == Some(&b'0') && bytes.get(1).is_some_and(is_base) {
+            return radix_literal(s);
+        }
+        // separators are checked once the literal is known
+        let digits = |from: usize| bytes[from..].iter().take_while(|b| b.is_ascii_digit() || **b == b'_').count();
+
+        let mut to_skip = digits(0);
+        let mut is_float = false;
+        // fractional part: `3.14`, `.5`, but not `3.`
+        if bytes.get(to_skip) == Some(&b'.') && bytes.get(to_skip + 1).is_some_and(u8::is_ascii_digit) {
+            to_skip += 1 + digits(to_skip + 1);
+            is_float = true;
+        }
+        // exponent: `1e9`, `2.5E-3`, only taken if it has digits
+        if to_skip != 0 && matches!(bytes.get(to_skip), Some(b'e' | b'E')) {
+            let sign = usize::from(matches!(bytes.get(to_skip + 1), Some(b'+' | b'-')));
+            let exp = digits(to_skip + 1 + sign);
+            if exp != 0 {
+                to_skip += 1 + sign + exp;
+                is_float = true;
             }
-            to_skip += 1;
         }
 
-        if to_skip != 0 {
-            (
-                Outcome::Token(tokens::Token::Number(tokens::Number(
-                    s[..to_skip].parse().expect("Empty number somehow"),
-                ))),
-                to_skip,
-            )
+        if to_skip == 0 {
+            return (Outcome::State(State::General(GeneralState {})), 0);
+        }
+
+        if !separated(&s[..to_skip], 10) {
+            return (Outcome::Error(TokenizeError::malformed_number(0)), to_skip);
+        }
+        let literal = s[..to_skip].replace('_', "");
+        let num = if is_float {
+            literal.parse().ok().filter(|num: &f64| num.is_finite()).map(tokens::Number::Float)
         } else {
-            (Outcome::State(State::General(GeneralState {})), 0)
+            parse_int(&literal, 10)
+        };
+        match num {
+            Some(num) => (Outcome::Token(tokens::Token::Number(num), tokens::Span::new(0, to_skip)), to_skip),
+            None => (Outcome::Error(TokenizeError::number_too_large(0)), to_skip),
+        }
+    }
+}
+
+/// `0xFF`, `0o17` or `0b1010`, the whole run of letters, digits and `_` is taken,
+/// so that `0xFG` is reported instead of being split into a number and a name
+fn radix_literal(s: &str) -> (Outcome, usize) {
+    let to_skip = s.find(|ch: char| !ch.is_ascii_alphanumeric() && ch != '_').unwrap_or(s.len());
+    let radix = match s.as_bytes()[1].to_ascii_lowercase() {
+        b'x' => 16,
+        b'o' => 8,
+        b'b' => 2,
+        _ => return (Outcome::Error(TokenizeError::malformed_number(0)), to_skip),
+    };
+    let digits = &s[2..to_skip];
+    if let Some(bad) = digits.find(|ch: char| ch != '_' && !ch.is_digit(radix)) {
+        return (Outcome::Error(TokenizeError::invalid_digit(2 + bad)), to_skip);
+    }
+    if digits.is_empty() || !separated(digits, radix) {
+        return (Outcome::Error(TokenizeError::malformed_number(0)), to_skip);
+    }
+    match parse_int(&digits.replace('_', ""), radix) {
+        Some(num) => {
+            let tok = tokens::Token::Number(num);
+            (Outcome::Token(tok, tokens::Span::new(0, to_skip)), to_skip)
+        }
+        None => (Outcome::Error(TokenizeError::number_too_large(0)), to_skip),
+    }
+}
+
+/// `_` may only stand between two digits: `1_000`, but not `1__0` or `1_`
+fn separated(literal: &str, radix: u32) -> bool {
+    let bytes = literal.as_bytes();
+    let is_digit = |idx: usize| bytes.get(idx).is_some_and(|b| char::from(*b).is_digit(radix));
+    (0..bytes.len())
+        .filter(|idx| bytes[*idx] == b'_')
+        .all(|idx| idx > 0 && is_digit(idx - 1) && is_digit(idx + 1))
+}
+
+/// Too large for [`tokens::Int`] is [`tokens::Number::Big`] with `bigint`, `None` otherwise
+fn parse_int(digits: &str, radix: u32) -> Option<tokens::Number> {
+    match tokens::Int::from_str_radix(digits, radix) {
+        Ok(num) => Some(tokens::Number::Int(num)),
+        #[cfg(feature = "bigint")]
+        Err(_) => num_traits::Num::from_str_radix(digits, radix).ok().map(tokens::Number::Big),
+        #[cfg(not(feature = "bigint"))]
+        Err(_) => None,
+    }
+}
+
+/// Value of a literal too large for [`tokens::Int`], as a float
+pub(crate) fn approximate(literal: &str) -> f64 {
+    let literal = literal.replace('_', "");
+    let radix = match literal.get(..2).map(str::to_ascii_lowercase).as_deref() {
+        Some("0x") => 16,
+        Some("0o") => 8,
+        Some("0b") => 2,
+        _ => return literal.parse().unwrap_or(f64::INFINITY),
+    };
+    literal[2..]
+        .chars()
+        .filter_map(|ch| ch.to_digit(radix))
+        .fold(0.0, |acc, digit| acc * f64::from(radix) + f64::from(digit))
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct IdentState {}
+
+impl ParseStep for IdentState {
+    fn process(&mut self, s: &str, grammar: &Grammar) -> (Outcome, usize) {
+        match s.chars().next() {
+            Some(ch) if is_ident_start(ch) => {
+                let to_skip = s.find(|ch| !is_ident_continue(ch)).unwrap_or(s.len());
+                let word = &s[..to_skip];
+                let tok = grammar
+                    .token(word)
+                    .or_else(|| builtin_oper(word).map(tokens::Token::Oper))
+                    .unwrap_or_else(|| match word {
+                        "not" => tokens::Token::UnaryOper(tokens::UnaryOperation::Not),
+                        name => tokens::Token::Ident(name.to_owned()),
+                    });
+                (Outcome::Token(tok, tokens::Span::new(0, to_skip)), to_skip)
+            }
+            _ => (Outcome::State(State::General(GeneralState {})), 0),
         }
     }
 }
@@ -128,43 +355,73 @@ impl ParseStep for NumberState {
 struct GeneralState {}
 
 impl ParseStep for GeneralState {
-    fn process(&mut self, s: &str) -> (Outcome, usize) {
+    fn process(&mut self, s: &str, grammar: &Grammar) -> (Outcome, usize) {
         let mut chars = s.chars();
         let mut to_skip = 0;
         loop {
+            let rest = chars.as_str();
             match chars.next() {
-                Some(ch) if ch.is_digit(10) => {
+                Some(ch) if ch.is_ascii_digit() || (ch == '.' && starts_with_digit(chars.as_str())) => {
                     break (
                         Outcome::State(State::Number(NumberState::default())),
                         to_skip,
                     )
                 }
+                Some(ch) if is_ident_start(ch) => {
+                    break (
+                        Outcome::State(State::Ident(IdentState::default())),
+                        to_skip,
+                    )
+                }
                 Some(ch) if ch.is_whitespace() => {
-                    to_skip += 1;
+                    // offsets are in bytes, think of a non-breaking space
+                    to_skip += ch.len_utf8();
+                }
+                Some(_) if is_oper(rest, grammar) => {
+                    let (tok, len) = try_parse_oper(rest, grammar).unwrap();
+                    break (
+                        Outcome::Token(tok, tokens::Span::new(to_skip, to_skip + len)),
+                        to_skip + len,
+                    )
+                }
+                // `!=` is taken above
+                Some(ch @ ('!' | '~')) => {
+                    let op = if ch == '!' { tokens::UnaryOperation::Not } else { tokens::UnaryOperation::BitNot };
+                    break (
+                        Outcome::Token(tokens::Token::UnaryOper(op), tokens::Span::new(to_skip, to_skip + 1)),
+                        to_skip + 1,
+                    )
                 }
-                Some(ch) if is_oper(ch) => {
+                Some(',') => {
                     break (
-                        Outcome::Token(tokens::Token::Oper(try_parse_oper(ch).unwrap())),
+                        Outcome::Token(tokens::Token::Comma, tokens::Span::new(to_skip, to_skip + 1)),
                         to_skip + 1,
                     )
                 }
+                Some(ch @ ('?' | ':')) => {
+                    let tok = if ch == '?' { tokens::Token::Question } else { tokens::Token::Colon };
+                    break (Outcome::Token(tok, tokens::Span::new(to_skip, to_skip + 1)), to_skip + 1)
+                }
                 Some(ch) if ch == '(' || ch == ')' => {
                     break (
-                        Outcome::Token(tokens::Token::Paren(if ch == '(' {
-                            tokens::Paren::Left
-                        } else {
-                            tokens::Paren::Right
-                        })),
+                        Outcome::Token(
+                            tokens::Token::Paren(if ch == '(' {
+                                tokens::Paren::Left
+                            } else {
+                                tokens::Paren::Right
+                            }),
+                            tokens::Span::new(to_skip, to_skip + 1),
+                        ),
                         to_skip + 1,
                     )
                 }
-                Some(_) => {
+                Some(ch) => {
                     break (
                         Outcome::Error(TokenizeError {
                             kind: TokenizeErrorKind::InvalidSymbol,
                             at: to_skip,
                         }),
-                        0,
+                        to_skip + ch.len_utf8(),
                     )
                 }
                 None => break (Outcome::Done, to_skip),
@@ -174,10 +431,11 @@ impl ParseStep for GeneralState {
 }
 
 impl ParseStep for State {
-    fn process(&mut self, s: &str) -> (Outcome, usize) {
+    fn process(&mut self, s: &str, grammar: &Grammar) -> (Outcome, usize) {
         match self {
-            State::Number(ns) => ns.process(s),
-            State::General(gs) => gs.process(s),
+            State::Number(ns) => ns.process(s, grammar),
+            State::Ident(is) => is.process(s, grammar),
+            State::General(gs) => gs.process(s, grammar),
         }
     }
 }
@@ -188,11 +446,15 @@ mod tests {
 
     use super::{tokenize, TokenizeError};
 
+    fn values(input: &str) -> Result<Vec<tokens::Token>, TokenizeError> {
+        tokenize(input).map(|tok| tok.map(|tok| tok.value)).collect()
+    }
+
     #[test]
     fn number() -> Result<(), TokenizeError> {
         let s = "123";
-        let res: Vec<_> = tokenize(s).collect::<Result<_, _>>()?;
-        [tokens::Token::Number(tokens::Number(123))]
+        let res = values(s)?;
+        [tokens::Token::Number(tokens::Number::Int(123.into()))]
             .into_iter()
             .zip(res)
             .for_each(|(l, r)| assert_eq!(l, r));
@@ -201,9 +463,9 @@ mod tests {
 
     #[test]
     fn number_and_op() -> Result<(), TokenizeError> {
-        let res: Vec<_> = tokenize("123 + (").collect::<Result<_, _>>()?;
+        let res = values("123 + (")?;
         [
-            tokens::Token::Number(tokens::Number(123)),
+            tokens::Token::Number(tokens::Number::Int(123.into())),
             tokens::Token::Oper(tokens::Operation::Add),
             tokens::Token::Paren(tokens::Paren::Left),
         ]
@@ -212,4 +474,251 @@ mod tests {
         .for_each(|(l, r)| assert_eq!(l, r));
         Ok(())
     }
+
+    #[test]
+    fn spans() -> Result<(), TokenizeError> {
+        let res: Vec<_> = tokenize(" 12 **(x1, .5)")
+            .map(|tok| tok.map(|tok| std::ops::Range::from(tok.span)))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(res, vec![1..3, 4..6, 6..7, 7..9, 9..10, 11..13, 13..14]);
+        assert_eq!(values("1 + 2 # 3"), Err(TokenizeError::invalid_symbol(6)));
+        Ok(())
+    }
+
+    #[test]
+    fn unicode() -> Result<(), TokenizeError> {
+        let res = values("2\u{a0}×\u{2009}größe − 1 ÷ π")?;
+        assert_eq!(
+            res,
+            vec![
+                tokens::Token::Number(tokens::Number::Int(2.into())),
+                tokens::Token::Oper(tokens::Operation::Mul),
+                tokens::Token::Ident("größe".to_owned()),
+                tokens::Token::Oper(tokens::Operation::Sub),
+                tokens::Token::Number(tokens::Number::Int(1.into())),
+                tokens::Token::Oper(tokens::Operation::Div),
+                tokens::Token::Ident("π".to_owned()),
+            ]
+        );
+
+        let spans: Vec<_> = tokenize("\u{a0}1 × x")
+            .map(|tok| tok.map(|tok| std::ops::Range::from(tok.span)))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(spans, vec![2..3, 4..6, 7..8]);
+        assert_eq!(values("1 \u{a0}€"), Err(TokenizeError::invalid_symbol(4)));
+        Ok(())
+    }
+
+    #[test]
+    fn position() {
+        let source = "1 +\n  größe *\n€";
+        assert_eq!(tokens::Position::locate(source, 0), tokens::Position { line: 1, column: 1 });
+        assert_eq!(tokens::Position::locate(source, 6), tokens::Position { line: 2, column: 3 });
+        assert_eq!(tokens::Position::locate(source, 14), tokens::Position { line: 2, column: 9 });
+        let err = values(source).unwrap_err();
+        assert_eq!(tokens::Position::locate(source, err.at()), tokens::Position { line: 3, column: 1 });
+    }
+
+    #[test]
+    fn after_error() {
+        let res: Vec<_> = tokenize("1 # 2 € 1e999 x").collect();
+        assert_eq!(
+            res.into_iter().map(|tok| tok.map(|tok| tok.value)).collect::<Vec<_>>(),
+            vec![
+                Ok(tokens::Token::Number(tokens::Number::Int(1.into()))),
+                Err(TokenizeError::invalid_symbol(2)),
+                Ok(tokens::Token::Number(tokens::Number::Int(2.into()))),
+                Err(TokenizeError::invalid_symbol(6)),
+                Err(TokenizeError::number_too_large(10)),
+                Ok(tokens::Token::Ident("x".to_owned())),
+            ]
+        );
+    }
+
+    #[test]
+    fn comparisons() -> Result<(), TokenizeError> {
+        use tokens::{Comparison, Operation};
+
+        let res = values("a<=b<c==d!=e>f>=g ≤ h")?;
+        let cmps: Vec<_> = res
+            .into_iter()
+            .filter_map(|tok| match tok {
+                tokens::Token::Oper(Operation::Cmp(cmp)) => Some(cmp),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            cmps,
+            vec![Comparison::Le, Comparison::Lt, Comparison::Eq, Comparison::Ne, Comparison::Gt, Comparison::Ge, Comparison::Le]
+        );
+        assert_eq!(values("1 = 2"), Err(TokenizeError::invalid_symbol(2)));
+        Ok(())
+    }
+
+    #[test]
+    fn logic() -> Result<(), TokenizeError> {
+        use tokens::{Logic, Operation, Token, UnaryOperation};
+
+        let res = values("!a&&b || not c and d or nothing")?;
+        assert_eq!(
+            res,
+            vec![
+                Token::UnaryOper(UnaryOperation::Not),
+                Token::Ident("a".to_owned()),
+                Token::Oper(Operation::Logic(Logic::And)),
+                Token::Ident("b".to_owned()),
+                Token::Oper(Operation::Logic(Logic::Or)),
+                Token::UnaryOper(UnaryOperation::Not),
+                Token::Ident("c".to_owned()),
+                Token::Oper(Operation::Logic(Logic::And)),
+                Token::Ident("d".to_owned()),
+                Token::Oper(Operation::Logic(Logic::Or)),
+                Token::Ident("nothing".to_owned()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bitwise() -> Result<(), TokenizeError> {
+        use tokens::{Bitwise, Comparison, Logic, Operation, Token, UnaryOperation};
+
+        let res = values("~a&b|c xor d<<e>>f<=g&&h||i")?;
+        let ident = |name: &str| Token::Ident(name.to_owned());
+        assert_eq!(
+            res,
+            vec![
+                Token::UnaryOper(UnaryOperation::BitNot),
+                ident("a"),
+                Token::Oper(Operation::Bit(Bitwise::And)),
+                ident("b"),
+                Token::Oper(Operation::Bit(Bitwise::Or)),
+                ident("c"),
+                Token::Oper(Operation::Bit(Bitwise::Xor)),
+                ident("d"),
+                Token::Oper(Operation::Bit(Bitwise::Shl)),
+                ident("e"),
+                Token::Oper(Operation::Bit(Bitwise::Shr)),
+                ident("f"),
+                Token::Oper(Operation::Cmp(Comparison::Le)),
+                ident("g"),
+                Token::Oper(Operation::Logic(Logic::And)),
+                ident("h"),
+                Token::Oper(Operation::Logic(Logic::Or)),
+                ident("i"),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn conditional() -> Result<(), TokenizeError> {
+        use tokens::{Comparison, Number, Operation, Token};
+
+        let res = values("n>1?n:-1")?;
+        assert_eq!(
+            res,
+            vec![
+                Token::Ident("n".to_owned()),
+                Token::Oper(Operation::Cmp(Comparison::Gt)),
+                Token::Number(Number::Int(1.into())),
+                Token::Question,
+                Token::Ident("n".to_owned()),
+                Token::Colon,
+                Token::Oper(Operation::Sub),
+                Token::Number(Number::Int(1.into())),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn idents() -> Result<(), TokenizeError> {
+        let res = values("price * qty_2 + _x1")?;
+        assert_eq!(
+            res,
+            vec![
+                tokens::Token::Ident("price".to_owned()),
+                tokens::Token::Oper(tokens::Operation::Mul),
+                tokens::Token::Ident("qty_2".to_owned()),
+                tokens::Token::Oper(tokens::Operation::Add),
+                tokens::Token::Ident("_x1".to_owned()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn floats() -> Result<(), TokenizeError> {
+        let res = values("2.75 + .5 * 1e-9 - 2.5E3")?;
+        assert_eq!(
+            res,
+            vec![
+                tokens::Token::Number(tokens::Number::Float(2.75)),
+                tokens::Token::Oper(tokens::Operation::Add),
+                tokens::Token::Number(tokens::Number::Float(0.5)),
+                tokens::Token::Oper(tokens::Operation::Mul),
+                tokens::Token::Number(tokens::Number::Float(1e-9)),
+                tokens::Token::Oper(tokens::Operation::Sub),
+                tokens::Token::Number(tokens::Number::Float(2500.0)),
+            ]
+        );
+
+        assert_eq!(values("1e999"), Err(TokenizeError::number_too_large(0)));
+        assert_eq!(values("1."), Err(TokenizeError::invalid_symbol(1)));
+        Ok(())
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn number_too_large() {
+        assert_eq!(values("1 + 99999999999999999999"), Err(TokenizeError::number_too_large(4)));
+    }
+
+    #[test]
+    fn radix() -> Result<(), TokenizeError> {
+        use tokens::{Number, Operation, Token};
+
+        let int = |num: i32| Token::Number(Number::Int(num.into()));
+        let res = values("0xFF + 0b1010 - 0O17*1_000_000 + .5_0 1e1_0")?;
+        assert_eq!(
+            res[..7],
+            [
+                int(255),
+                Token::Oper(Operation::Add),
+                int(10),
+                Token::Oper(Operation::Sub),
+                int(15),
+                Token::Oper(Operation::Mul),
+                int(1_000_000),
+            ]
+        );
+        assert_eq!(res[8..], [Token::Number(Number::Float(0.5)), Token::Number(Number::Float(1e10))]);
+
+        for (input, err) in [
+            ("0b102", TokenizeError::invalid_digit(4)),
+            ("1 + 0o8", TokenizeError::invalid_digit(6)),
+            ("0xFG", TokenizeError::invalid_digit(3)),
+            ("0x", TokenizeError::malformed_number(0)),
+            ("2 * 0z12", TokenizeError::malformed_number(4)),
+            ("1__000", TokenizeError::malformed_number(0)),
+            ("1_000_", TokenizeError::malformed_number(0)),
+            ("1_.5", TokenizeError::malformed_number(0)),
+            ("0b_", TokenizeError::malformed_number(0)),
+        ] {
+            assert_eq!(values(input), Err(err), "{input}");
+        }
+        // the bad literal is skipped as a whole
+        assert_eq!(tokenize("0xFG + 1").filter(Result::is_ok).count(), 2);
+        Ok(())
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn big_number() -> Result<(), TokenizeError> {
+        let res = values("99999999999999999999")?;
+        let expected: tokens::BigInt = "99999999999999999999".parse().unwrap();
+        assert_eq!(res, vec![tokens::Token::Number(tokens::Number::Big(expected))]);
+        Ok(())
+    }
 }
@@ -1,4 +1,6 @@
-use std::{ops, fmt::{Display, Write}};
+use std::{cmp::Ordering, ops::{self, Range}, fmt::{Display, Write}, sync::Arc};
+
+use crate::{EvalError, OverflowPolicy};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Paren {
@@ -6,73 +8,810 @@ pub enum Paren {
     Right,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Operation {
     Add,
     Sub,
     Mul,
     Div,
+    /// Remainder of [`Operation::FloorDiv`], takes the sign of the divisor: `-7 % 3 == 2`
+    Rem,
+    /// Division rounding towards negative infinity: `-7 // 2 == -4`
+    FloorDiv,
+    Pow,
+    /// Gives a boolean, so it is evaluated with [`Comparison::compare`] instead of [`Operation::apply`]
+    Cmp(Comparison),
+    /// Takes booleans, evaluated with [`Logic::apply`]; the right operand is
+    /// skipped when the left one decides the result
+    Logic(Logic),
+    /// Takes integers only, evaluated with [`Bitwise::apply_int`]
+    Bit(Bitwise),
+    /// Registered in a [`crate::grammar::Grammar`], which evaluates it
+    Custom(CustomOp),
+}
+
+/// How operators of the same precedence group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assoc {
+    /// `8 / 4 / 2` is `(8 / 4) / 2`
+    Left,
+    /// `2 ^ 3 ^ 2` is `2 ^ (3 ^ 2)`
+    Right,
+}
+
+/// Operator registered in a [`crate::grammar::Grammar`], carries what parsing
+/// and printing need, while its function stays in the grammar
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomOp {
+    pub(crate) symbol: Arc<str>,
+    pub(crate) prio: i32,
+    pub(crate) assoc: Assoc,
+}
+
+impl CustomOp {
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+}
+
+/// Operations on two's complement bits, with C precedence:
+/// `a | b ^ c & d` is `a | (b ^ (c & d))`, shifts bind tighter than comparisons
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bitwise {
+    And,
+    Or,
+    Xor,
+    Shl,
+    /// Arithmetic shift, the sign is kept: `-8 >> 1 == -4`
+    Shr,
+}
+
+impl Bitwise {
+    fn prio(&self) -> i32 {
+        match self {
+            Bitwise::Or => 3,
+            Bitwise::Xor => 4,
+            Bitwise::And => 5,
+            Bitwise::Shl | Bitwise::Shr => 8,
+        }
+    }
+
+    /// Bits shifted out are lost, negative shifts and ones by the width
+    /// of [`Int`] or more are errors
+    pub fn apply_int(self, lhs: Int, rhs: Int) -> Result<Int, EvalError> {
+        match self {
+            Bitwise::And => Ok(lhs & rhs),
+            Bitwise::Or => Ok(lhs | rhs),
+            Bitwise::Xor => Ok(lhs ^ rhs),
+            Bitwise::Shl => Ok(lhs << shift_amount(rhs)?),
+            Bitwise::Shr => Ok(lhs >> shift_amount(rhs)?),
+        }
+    }
+
+    /// Same as [`Bitwise::apply_int`], big integers have no width, but the amount
+    /// is limited like a power exponent, and a left shift past [`MAX_BIG_BITS`] overflows
+    #[cfg(feature = "bigint")]
+    pub fn apply_big(self, lhs: BigInt, rhs: BigInt) -> Result<BigInt, EvalError> {
+        let amount = || u32::try_from(&rhs).map_err(|_| EvalError::ShiftOutOfRange);
+        match self {
+            Bitwise::And => Ok(lhs & &rhs),
+            Bitwise::Or => Ok(lhs | &rhs),
+            Bitwise::Xor => Ok(lhs ^ &rhs),
+            Bitwise::Shl => {
+                let amount = amount()?;
+                if lhs.bits() + u64::from(amount) > MAX_BIG_BITS {
+                    return Err(EvalError::Overflow);
+                }
+                Ok(lhs << amount)
+            }
+            Bitwise::Shr => Ok(lhs >> amount()?),
+        }
+    }
+}
+
+fn shift_amount(amount: Int) -> Result<u32, EvalError> {
+    u32::try_from(amount).ok().filter(|amount| *amount < Int::BITS).ok_or(EvalError::ShiftOutOfRange)
+}
+
+impl Display for Bitwise {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sym = match self {
+            Bitwise::And => "&",
+            Bitwise::Or => "|",
+            // `^` is a power
+            Bitwise::Xor => "xor",
+            Bitwise::Shl => "<<",
+            Bitwise::Shr => ">>",
+        };
+        f.write_str(sym)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Logic {
+    And,
+    Or,
+}
+
+impl Logic {
+    /// `a || b && c` is `a || (b && c)`, both are below bitwise operators
+    fn prio(&self) -> i32 {
+        match self {
+            Logic::Or => 1,
+            Logic::And => 2,
+        }
+    }
+
+    pub fn apply(self, lhs: bool, rhs: bool) -> bool {
+        match self {
+            Logic::And => lhs && rhs,
+            Logic::Or => lhs || rhs,
+        }
+    }
+
+    /// Result that doesn't depend on the right operand
+    pub fn short_circuit(self, lhs: bool) -> Option<bool> {
+        match (self, lhs) {
+            (Logic::And, false) | (Logic::Or, true) => Some(lhs),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Logic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Logic::And => f.write_str("&&"),
+            Logic::Or => f.write_str("||"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    /// Equality is below ordering, as in C: `a < b == c < d` is `(a < b) == (c < d)`
+    fn prio(&self) -> i32 {
+        match self {
+            Comparison::Eq | Comparison::Ne => 6,
+            Comparison::Lt | Comparison::Le | Comparison::Gt | Comparison::Ge => 7,
+        }
+    }
+
+    pub fn is_equality(&self) -> bool {
+        matches!(self, Comparison::Eq | Comparison::Ne)
+    }
+
+    /// Unordered operands, like NaN, are only unequal
+    pub fn compare<T: PartialOrd>(self, lhs: &T, rhs: &T) -> bool {
+        let ord = lhs.partial_cmp(rhs);
+        match self {
+            Comparison::Eq => ord == Some(Ordering::Equal),
+            Comparison::Ne => ord != Some(Ordering::Equal),
+            Comparison::Lt => ord == Some(Ordering::Less),
+            Comparison::Le => matches!(ord, Some(Ordering::Less | Ordering::Equal)),
+            Comparison::Gt => ord == Some(Ordering::Greater),
+            Comparison::Ge => matches!(ord, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sym = match self {
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        };
+        f.write_str(sym)
+    }
+}
+
+/// Exponentiation, the only [`Operation`] without a `std::ops` trait
+pub trait Power: Sized {
+    fn power(self, exp: Self) -> Result<Self, EvalError>;
+}
+
+impl Power for f64 {
+    fn power(self, exp: Self) -> Result<Self, EvalError> {
+        Ok(self.powf(exp))
+    }
+}
+
+/// Negative exponents and overflow are errors, see [`Operation::apply_int`] for other policies
+impl Power for i64 {
+    fn power(self, exp: Self) -> Result<Self, EvalError> {
+        match trivial_power(self, exp)? {
+            Some(res) => Ok(res),
+            None => self.checked_pow(pow_exponent(exp)?).ok_or(EvalError::Overflow),
+        }
+    }
+}
+
+/// Negative exponents are errors, as are results beyond [`MAX_BIG_BITS`]
+#[cfg(feature = "bigint")]
+impl Power for BigInt {
+    fn power(self, exp: Self) -> Result<Self, EvalError> {
+        big_exponent(&self, exp).map(|exp| self.pow(exp))
+    }
+}
+
+/// Floored division and remainder, `std::ops` only has truncating ones
+pub trait FloorDivision {
+    fn floor_div(self, rhs: Self) -> Self;
+    fn floor_rem(self, rhs: Self) -> Self;
+    /// Dividing by it is an error, floats give an infinity or NaN instead
+    fn is_zero_divisor(&self) -> bool;
+}
+
+impl FloorDivision for f64 {
+    fn floor_div(self, rhs: Self) -> Self {
+        (self / rhs).floor()
+    }
+
+    fn floor_rem(self, rhs: Self) -> Self {
+        let rem = self % rhs;
+        if rem != 0.0 && (rem < 0.0) != (rhs < 0.0) {
+            rem + rhs
+        } else {
+            rem
+        }
+    }
+
+    fn is_zero_divisor(&self) -> bool {
+        false
+    }
+}
+
+impl FloorDivision for i64 {
+    fn floor_div(self, rhs: Self) -> Self {
+        self.wrapping_div(rhs) - floor_adjust(self, rhs)
+    }
+
+    fn floor_rem(self, rhs: Self) -> Self {
+        // `i64::MIN % -1` is just 0
+        self.wrapping_rem(rhs) + rhs * floor_adjust(self, rhs)
+    }
+
+    fn is_zero_divisor(&self) -> bool {
+        *self == 0
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl FloorDivision for BigInt {
+    fn floor_div(self, rhs: Self) -> Self {
+        num_integer::Integer::div_floor(&self, &rhs)
+    }
+
+    fn floor_rem(self, rhs: Self) -> Self {
+        num_integer::Integer::mod_floor(&self, &rhs)
+    }
+
+    fn is_zero_divisor(&self) -> bool {
+        self.sign() == num_bigint::Sign::NoSign
+    }
+}
+
+/// 1 if truncating division of `lhs` by `rhs` differs from the floored one
+fn floor_adjust(lhs: i64, rhs: i64) -> i64 {
+    i64::from(lhs.wrapping_rem(rhs) != 0 && (lhs < 0) != (rhs < 0))
+}
+
+fn pow_exponent(exp: Int) -> Result<u32, EvalError> {
+    if exp < 0 {
+        return Err(EvalError::NegativeExponent);
+    }
+    u32::try_from(exp).map_err(|_| EvalError::Overflow)
+}
+
+/// Powers of 0, 1 and -1, which take an exponent of any size, `None` for other bases
+fn trivial_power(base: Int, exp: Int) -> Result<Option<Int>, EvalError> {
+    if exp < 0 {
+        return Err(EvalError::NegativeExponent);
+    }
+    Ok(match base {
+        0 => Some(Int::from(exp == 0)),
+        1 => Some(1),
+        -1 => Some(if exp % 2 == 0 { 1 } else { -1 }),
+        _ => None,
+    })
+}
+
+/// `wrapping_pow` for exponents beyond `u32`
+fn wrapping_power(mut base: Int, mut exp: u64) -> Int {
+    let mut res: Int = 1;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            res = res.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exp >>= 1;
+    }
+    res
+}
+
+/// Size of the largest big integer a power or a left shift may produce, about 300 000 decimal digits
+#[cfg(feature = "bigint")]
+pub const MAX_BIG_BITS: u64 = 1 << 20;
+
+#[cfg(feature = "bigint")]
+fn big_exponent(base: &BigInt, exp: BigInt) -> Result<u32, EvalError> {
+    if exp.sign() == num_bigint::Sign::Minus {
+        return Err(EvalError::NegativeExponent);
+    }
+    let exp = match u32::try_from(&exp) {
+        Ok(exp) => exp,
+        // 0 and ±1 only care whether a nonzero exponent is even
+        Err(_) if base.bits() <= 1 => 2 - u32::from(exp.bit(0)),
+        Err(_) => return Err(EvalError::Overflow),
+    };
+    // the result takes at least `(bits - 1) * exp` bits, so 0 and ±1 are fine with any exponent
+    if (base.bits().saturating_sub(1)).saturating_mul(u64::from(exp)) > MAX_BIG_BITS {
+        return Err(EvalError::Overflow);
+    }
+    Ok(exp)
 }
 
 impl Operation {
     pub(crate) fn prio(&self) -> i32 {
         match self {
-            Operation::Add | Operation::Sub => 1,
-            Operation::Mul | Operation::Div => 2,
+            Operation::Logic(logic) => logic.prio(),
+            Operation::Cmp(cmp) => cmp.prio(),
+            Operation::Bit(bit) => bit.prio(),
+            Operation::Add | Operation::Sub => 9,
+            Operation::Mul | Operation::Div | Operation::Rem | Operation::FloorDiv => 10,
+            // above unary operators: `-2^2` is `-(2^2)`
+            Operation::Pow => 12,
+            Operation::Custom(op) => op.prio,
         }
     }
 
-    fn as_fun<T>(self) -> fn(T, T) -> T
-    where
-        T: ops::Add<T, Output = T> + ops::Sub<T, Output = T>,
-        T: ops::Mul<T, Output = T> + ops::Div<T, Output = T>,
-    {
+    fn is_division(&self) -> bool {
+        matches!(self, Operation::Div | Operation::Rem | Operation::FloorDiv)
+    }
+
+    /// `2^3^2` is `2^(3^2)`, while `8/4/2` is `(8/4)/2`
+    pub(crate) fn is_right_assoc(&self) -> bool {
         match self {
-            Operation::Add => ops::Add::add,
-            Operation::Sub => ops::Sub::sub,
-            Operation::Mul => ops::Mul::mul,
-            Operation::Div => ops::Div::div,
+            Operation::Custom(op) => op.assoc == Assoc::Right,
+            _ => matches!(self, Operation::Pow),
         }
     }
 
-    pub fn apply<T>(self, lhs: T, rhs: T) -> T
+    /// Fails with [`EvalError::NotArithmetic`] on [`Operation::Cmp`], see [`Comparison::compare`],
+    /// or [`Operation::Bit`], see [`Bitwise::apply_int`], and with [`EvalError::NotABool`]
+    /// on [`Operation::Logic`], see [`Logic::apply`]. Custom operators are evaluated by their grammar,
+    /// here they fail with [`EvalError::UnknownOperator`]. Integer division by zero
+    /// fails with [`EvalError::DivisionByZero`]
+    pub fn apply<T>(&self, lhs: T, rhs: T) -> Result<T, EvalError>
     where
         T: ops::Add<T, Output = T> + ops::Sub<T, Output = T>,
         T: ops::Mul<T, Output = T> + ops::Div<T, Output = T>,
+        T: Power + FloorDivision,
     {
-        self.as_fun()(lhs, rhs)
+        match self {
+            _ if self.is_division() && rhs.is_zero_divisor() => Err(EvalError::DivisionByZero),
+            Operation::Add => Ok(lhs + rhs),
+            Operation::Sub => Ok(lhs - rhs),
+            Operation::Mul => Ok(lhs * rhs),
+            Operation::Div => Ok(lhs / rhs),
+            Operation::Rem => Ok(lhs.floor_rem(rhs)),
+            Operation::FloorDiv => Ok(lhs.floor_div(rhs)),
+            Operation::Pow => lhs.power(rhs),
+            Operation::Cmp(_) => Err(EvalError::NotArithmetic),
+            Operation::Logic(_) => Err(EvalError::NotABool),
+            Operation::Bit(_) => Err(EvalError::NotArithmetic),
+            Operation::Custom(op) => Err(EvalError::UnknownOperator(op.symbol().to_owned())),
+        }
+    }
+
+    /// Same as [`Operation::apply`] on [`Int`], but division by zero
+    /// and negative exponents are always errors, overflow is handled according to `overflow`
+    pub fn apply_int(&self, lhs: Int, rhs: Int, overflow: OverflowPolicy) -> Result<Int, EvalError> {
+        if self.is_division() && rhs == 0 {
+            return Err(EvalError::DivisionByZero);
+        }
+        if let Operation::Bit(bit) = self {
+            return bit.apply_int(lhs, rhs);
+        }
+        match overflow {
+            // the caller goes on with big integers
+            #[cfg(feature = "bigint")]
+            OverflowPolicy::Promote => self.apply_int(lhs, rhs, OverflowPolicy::Checked),
+            OverflowPolicy::Checked => {
+                let res = match self {
+                    Operation::Add => lhs.checked_add(rhs),
+                    Operation::Sub => lhs.checked_sub(rhs),
+                    Operation::Mul => lhs.checked_mul(rhs),
+                    Operation::Div => lhs.checked_div(rhs),
+                    // `i64::MIN % -1` is just 0, as under the other policies
+                    Operation::Rem => Some(lhs.wrapping_rem(rhs) + rhs * floor_adjust(lhs, rhs)),
+                    Operation::FloorDiv => lhs.checked_div(rhs).map(|quot| quot - floor_adjust(lhs, rhs)),
+                    Operation::Pow => match trivial_power(lhs, rhs)? {
+                        Some(res) => Some(res),
+                        None => lhs.checked_pow(pow_exponent(rhs)?),
+                    },
+                    Operation::Cmp(_) | Operation::Logic(_) | Operation::Bit(_) | Operation::Custom(_) => {
+                        return self.apply(lhs, rhs)
+                    }
+                };
+                res.ok_or(EvalError::Overflow)
+            }
+            OverflowPolicy::Wrapping => Ok(match self {
+                Operation::Add => lhs.wrapping_add(rhs),
+                Operation::Sub => lhs.wrapping_sub(rhs),
+                Operation::Mul => lhs.wrapping_mul(rhs),
+                Operation::Div => lhs.wrapping_div(rhs),
+                Operation::Rem => lhs.wrapping_rem(rhs) + rhs * floor_adjust(lhs, rhs),
+                Operation::FloorDiv => lhs.wrapping_div(rhs) - floor_adjust(lhs, rhs),
+                Operation::Pow => match trivial_power(lhs, rhs)? {
+                    Some(res) => res,
+                    None => wrapping_power(lhs, rhs as u64),
+                },
+                Operation::Cmp(_) | Operation::Logic(_) | Operation::Bit(_) | Operation::Custom(_) => {
+                    return self.apply(lhs, rhs)
+                }
+            }),
+            OverflowPolicy::Saturating => Ok(match self {
+                Operation::Add => lhs.saturating_add(rhs),
+                Operation::Sub => lhs.saturating_sub(rhs),
+                Operation::Mul => lhs.saturating_mul(rhs),
+                Operation::Div => lhs.saturating_div(rhs),
+                // can't overflow, `i64::MIN % -1` is just 0
+                Operation::Rem => lhs.wrapping_rem(rhs) + rhs * floor_adjust(lhs, rhs),
+                Operation::FloorDiv => lhs.saturating_div(rhs) - floor_adjust(lhs, rhs),
+                Operation::Pow => match (trivial_power(lhs, rhs)?, u32::try_from(rhs)) {
+                    (Some(res), _) => res,
+                    (None, Ok(exp)) => lhs.saturating_pow(exp),
+                    (None, Err(_)) if lhs < 0 && rhs % 2 == 1 => Int::MIN,
+                    (None, Err(_)) => Int::MAX,
+                },
+                Operation::Cmp(_) | Operation::Logic(_) | Operation::Bit(_) | Operation::Custom(_) => {
+                    return self.apply(lhs, rhs)
+                }
+            }),
+        }
+    }
+
+    /// Same as [`Operation::apply_int`] on big integers, which only overflow
+    /// when a power or a left shift would take more than [`MAX_BIG_BITS`]
+    #[cfg(feature = "bigint")]
+    pub fn apply_big(&self, lhs: BigInt, rhs: BigInt) -> Result<BigInt, EvalError> {
+        match self {
+            Operation::Bit(bit) => bit.apply_big(lhs, rhs),
+            _ => self.apply(lhs, rhs),
+        }
     }
 }
 
 impl Display for Operation {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let ch = match self {
-            Operation::Add => '+',
-            Operation::Sub => '-',
-            Operation::Mul => '*',
-            Operation::Div => '/',
+        let sym = match self {
+            Operation::Add => "+",
+            Operation::Sub => "-",
+            Operation::Mul => "*",
+            Operation::Div => "/",
+            Operation::Rem => "%",
+            Operation::FloorDiv => "//",
+            Operation::Pow => "^",
+            Operation::Cmp(cmp) => return cmp.fmt(f),
+            Operation::Logic(logic) => return logic.fmt(f),
+            Operation::Bit(bit) => return bit.fmt(f),
+            Operation::Custom(op) => &op.symbol,
         };
-        f.write_char(ch)
+        f.write_str(sym)
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct Number(pub i64);
+/// Prefix operator, the parser tells it from [`Operation`] by context
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnaryOperation {
+    Neg,
+    Plus,
+    /// Takes a boolean, written as `!` or `not`
+    Not,
+    /// Flips all bits of an integer
+    BitNot,
+    /// Registered in a [`crate::grammar::Grammar`], which evaluates it
+    Custom(CustomOp),
+}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+impl UnaryOperation {
+    /// Binds tighter than any binary [`Operation`] but power, so `-2 ^ 2` is `-(2 ^ 2)`
+    pub(crate) fn prio(&self) -> i32 {
+        match self {
+            UnaryOperation::Custom(op) => op.prio,
+            _ => 11,
+        }
+    }
+
+    /// As written in infix, [`Display`] tells it from the binary one
+    pub(crate) fn symbol(&self) -> &str {
+        match self {
+            UnaryOperation::Neg => "-",
+            UnaryOperation::Plus => "+",
+            UnaryOperation::Not => "!",
+            UnaryOperation::BitNot => "~",
+            UnaryOperation::Custom(op) => &op.symbol,
+        }
+    }
+
+    pub(crate) fn from_binary(op: &Operation) -> Option<Self> {
+        match op {
+            Operation::Add => Some(UnaryOperation::Plus),
+            Operation::Sub => Some(UnaryOperation::Neg),
+            _ => None,
+        }
+    }
+
+    /// Fails with [`EvalError::NotABool`] on [`UnaryOperation::Not`] and with [`EvalError::NotArithmetic`]
+    /// on [`UnaryOperation::BitNot`], see [`UnaryOperation::apply_int`]. Custom operators are evaluated
+    /// by their grammar, here they fail with [`EvalError::UnknownOperator`]
+    pub fn apply<T: ops::Neg<Output = T>>(&self, arg: T) -> Result<T, EvalError> {
+        match self {
+            UnaryOperation::Neg => Ok(-arg),
+            UnaryOperation::Plus => Ok(arg),
+            UnaryOperation::Not => Err(EvalError::NotABool),
+            UnaryOperation::BitNot => Err(EvalError::NotArithmetic),
+            UnaryOperation::Custom(op) => Err(EvalError::UnknownOperator(op.symbol().to_owned())),
+        }
+    }
+
+    /// Same as [`UnaryOperation::apply`] on [`Int`], negation of the minimal
+    /// value is handled according to `overflow`
+    pub fn apply_int(&self, arg: Int, overflow: OverflowPolicy) -> Result<Int, EvalError> {
+        match (self, overflow) {
+            (UnaryOperation::Not, _) => Err(EvalError::NotABool),
+            (UnaryOperation::BitNot, _) => Ok(!arg),
+            (UnaryOperation::Plus | UnaryOperation::Custom(_), _) => self.apply(arg),
+            (UnaryOperation::Neg, OverflowPolicy::Checked) => arg.checked_neg().ok_or(EvalError::Overflow),
+            // the caller goes on with big integers
+            #[cfg(feature = "bigint")]
+            (UnaryOperation::Neg, OverflowPolicy::Promote) => arg.checked_neg().ok_or(EvalError::Overflow),
+            (UnaryOperation::Neg, OverflowPolicy::Wrapping) => Ok(arg.wrapping_neg()),
+            (UnaryOperation::Neg, OverflowPolicy::Saturating) => Ok(arg.saturating_neg()),
+        }
+    }
+
+    /// Same as [`UnaryOperation::apply_int`] on big integers, which never overflow
+    #[cfg(feature = "bigint")]
+    pub fn apply_big(&self, arg: BigInt) -> Result<BigInt, EvalError> {
+        match self {
+            UnaryOperation::Not => Err(EvalError::NotABool),
+            UnaryOperation::BitNot => Ok(!arg),
+            _ => self.apply(arg),
+        }
+    }
+}
+
+impl Display for UnaryOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnaryOperation::Neg => f.write_str("u-"),
+            UnaryOperation::Plus => f.write_str("u+"),
+            // can't be mistaken for a binary one
+            UnaryOperation::Not => f.write_str("!"),
+            UnaryOperation::BitNot => f.write_str("~"),
+            UnaryOperation::Custom(op) => f.write_str(&op.symbol),
+        }
+    }
+}
+
+/// Integer type of literals and evaluation results
+pub type Int = i64;
+
+/// Integers beyond [`Int`], see [`Number::Big`]
+#[cfg(feature = "bigint")]
+pub use num_bigint::BigInt;
+
+/// More variants may come with features, like [`Number::Big`] with `bigint`
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Number {
+    Int(Int),
+    Float(f64),
+    /// Integer literal too large for [`Int`]
+    #[cfg(feature = "bigint")]
+    Big(BigInt),
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Int(num) => f.write_fmt(format_args!("{num}")),
+            // `Debug` keeps the fractional part for whole numbers: `3.0`, not `3`
+            Number::Float(num) => f.write_fmt(format_args!("{num:?}")),
+            #[cfg(feature = "bigint")]
+            Number::Big(num) => f.write_fmt(format_args!("{num}")),
+        }
+    }
+}
+
+/// Integer of either size, as given to [`FmtInt`]
+pub(crate) enum IntRef<'n> {
+    Int(&'n Int),
+    #[cfg(feature = "bigint")]
+    Big(&'n BigInt),
+}
+
+/// Prints an integer in the base of the formatting trait it comes from
+pub(crate) type FmtInt = fn(IntRef<'_>, &mut std::fmt::Formatter<'_>) -> std::fmt::Result;
+
+/// Implements `Binary`, `Octal`, `LowerHex` and `UpperHex` with the type's
+/// `fmt_radix(&self, f, FmtInt)`. The sign goes first, `{:#x}` gives `-0x1f`
+/// rather than two's complement, so that big integers print the same way
+macro_rules! radix_fmt {
+    ($ty:ty) => {
+        $crate::tokens::radix_fmt!($ty: Binary, Octal, LowerHex, UpperHex);
+    };
+    ($ty:ty: $($radix:ident),*) => {$(
+        impl std::fmt::$radix for $ty {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                self.fmt_radix(f, |num, f| match num {
+                    $crate::tokens::IntRef::Int(num) => {
+                        if *num < 0 {
+                            f.write_str("-")?;
+                        }
+                        std::fmt::$radix::fmt(&num.unsigned_abs(), f)
+                    }
+                    #[cfg(feature = "bigint")]
+                    $crate::tokens::IntRef::Big(num) => {
+                        if num.sign() == num_bigint::Sign::Minus {
+                            f.write_str("-")?;
+                        }
+                        std::fmt::$radix::fmt(num.magnitude(), f)
+                    }
+                })
+            }
+        }
+    )*};
+}
+pub(crate) use radix_fmt;
+
+impl Number {
+    /// Floats stay decimal
+    fn fmt_radix(&self, f: &mut std::fmt::Formatter<'_>, fmt_int: FmtInt) -> std::fmt::Result {
+        match self {
+            Number::Int(num) => fmt_int(IntRef::Int(num), f),
+            Number::Float(_) => Display::fmt(self, f),
+            #[cfg(feature = "bigint")]
+            Number::Big(num) => fmt_int(IntRef::Big(num), f),
+        }
+    }
+}
+
+radix_fmt!(Number);
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Number(Number),
+    /// Variable name, resolved at evaluation
+    Ident(String),
     Paren(Paren),
     Oper(Operation),
+    UnaryOper(UnaryOperation),
+    /// Separator of function call arguments
+    Comma,
+    /// `?` of `cond ? a : b`, in postfix form it takes all three operands
+    Question,
+    /// `:` of `cond ? a : b`, the parser drops it
+    Colon,
+    /// Function application in postfix form, produced by the parser
+    Call { name: String, arity: usize },
 }
 
 impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Token::Number(Number(num)) => f.write_fmt(format_args!("{num}")),
+            Token::Number(num) => f.write_fmt(format_args!("{num}")),
+            Token::Ident(name) => f.write_str(name),
             Token::Paren(Paren::Left) => f.write_char('('),
             Token::Paren(Paren::Right) => f.write_char(')'),
             Token::Oper(op) => f.write_fmt(format_args!("{op}")),
+            Token::UnaryOper(op) => f.write_fmt(format_args!("{op}")),
+            Token::Comma => f.write_char(','),
+            Token::Question => f.write_char('?'),
+            Token::Colon => f.write_char(':'),
+            Token::Call { name, arity } => f.write_fmt(format_args!("{name}/{arity}")),
         }
     }
 }
+
+impl Token {
+    pub(crate) fn fmt_radix(&self, f: &mut std::fmt::Formatter<'_>, fmt_int: FmtInt) -> std::fmt::Result {
+        match self {
+            Token::Number(num) => num.fmt_radix(f, fmt_int),
+            _ => Display::fmt(self, f),
+        }
+    }
+}
+
+radix_fmt!(Token);
+
+/// Byte range in the source string
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Smallest span covering both
+    pub fn join(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    pub(crate) fn shift(self, by: usize) -> Span {
+        Span::new(self.start + by, self.end + by)
+    }
+}
+
+impl From<Span> for Range<usize> {
+    fn from(span: Span) -> Self {
+        span.start..span.end
+    }
+}
+
+/// Human-readable place in the source string, both numbers start from 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    /// Counted in chars, not bytes
+    pub column: usize,
+}
+
+impl Position {
+    /// Position of byte `offset` in `source`, offsets past the end point
+    /// right after the last char
+    pub fn locate(source: &str, offset: usize) -> Self {
+        let before = &source[..source.floor_char_boundary(offset)];
+        let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}:{}", self.line, self.column))
+    }
+}
+
+/// Value together with its place in the source string
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(value: T, span: Span) -> Self {
+        Self { value, span }
+    }
+}
@@ -0,0 +1,175 @@
+use std::{cmp::Ordering, fmt::Display};
+
+#[cfg(feature = "bigint")]
+use crate::tokens::BigInt;
+use crate::{
+    tokens::{self, FmtInt, Int, IntRef, Number, Operation, UnaryOperation},
+    EvalError, OverflowPolicy,
+};
+
+/// Result of evaluation: stays integer until some float gets involved,
+/// comparisons give booleans
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Value {
+    Int(Int),
+    Float(f64),
+    Bool(bool),
+    /// Integer beyond [`Int`], results that fit into it are always [`Value::Int`]
+    #[cfg(feature = "bigint")]
+    Big(BigInt),
+}
+
+impl Value {
+    /// Booleans are 1 and 0
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            Value::Int(num) => *num as f64,
+            Value::Float(num) => *num,
+            Value::Bool(flag) => f64::from(u8::from(*flag)),
+            #[cfg(feature = "bigint")]
+            Value::Big(num) => num_traits::ToPrimitive::to_f64(num).unwrap_or(f64::NAN),
+        }
+    }
+
+    /// Big integer, or a plain one if it fits
+    #[cfg(feature = "bigint")]
+    fn normalized(num: BigInt) -> Value {
+        i64::try_from(&num).map_or(Value::Big(num), Value::Int)
+    }
+
+    /// Integer operands are evaluated with [`Operation::apply_int`], or [`Operation::apply_big`]
+    /// if one is big or the overflow policy promotes them, anything else is promoted
+    /// to `f64` and follows IEEE 754, e.g. `1.0 / 0` is infinity.
+    /// Booleans may only be compared for equality and go to logical operations,
+    /// bitwise ones take integers only. Custom operators are evaluated by their grammar,
+    /// here they are unknown
+    pub fn apply(op: Operation, lhs: Value, rhs: Value, overflow: OverflowPolicy) -> Result<Value, EvalError> {
+        match (op, lhs, rhs) {
+            (Operation::Custom(op), _, _) => Err(EvalError::UnknownOperator(op.symbol().to_owned())),
+            (Operation::Logic(logic), Value::Bool(lhs), Value::Bool(rhs)) => Ok(Value::Bool(logic.apply(lhs, rhs))),
+            (Operation::Logic(_), _, _) => Err(EvalError::NotABool),
+            (Operation::Cmp(cmp), Value::Bool(lhs), Value::Bool(rhs)) if cmp.is_equality() => {
+                Ok(Value::Bool(cmp.compare(&lhs, &rhs)))
+            }
+            (_, Value::Bool(_), _) | (_, _, Value::Bool(_)) => Err(EvalError::NotANumber),
+            (Operation::Cmp(cmp), lhs, rhs) => Ok(Value::Bool(cmp.compare(&lhs, &rhs))),
+            (op, Value::Int(lhs), Value::Int(rhs)) => match op.apply_int(lhs, rhs, overflow) {
+                #[cfg(feature = "bigint")]
+                Err(EvalError::Overflow) if overflow == OverflowPolicy::Promote => {
+                    op.apply_big(lhs.into(), rhs.into()).map(Value::normalized)
+                }
+                res => res.map(Value::Int),
+            },
+            #[cfg(feature = "bigint")]
+            (op, Value::Big(lhs), Value::Int(rhs)) => op.apply_big(lhs, rhs.into()).map(Value::normalized),
+            #[cfg(feature = "bigint")]
+            (op, Value::Int(lhs), Value::Big(rhs)) => op.apply_big(lhs.into(), rhs).map(Value::normalized),
+            #[cfg(feature = "bigint")]
+            (op, Value::Big(lhs), Value::Big(rhs)) => op.apply_big(lhs, rhs).map(Value::normalized),
+            (Operation::Bit(_), _, _) => Err(EvalError::BitwiseOnFloat),
+            (op, lhs, rhs) => op.apply(lhs.as_f64(), rhs.as_f64()).map(Value::Float),
+        }
+    }
+
+    pub fn apply_unary(op: UnaryOperation, arg: Value, overflow: OverflowPolicy) -> Result<Value, EvalError> {
+        match (op, arg) {
+            (UnaryOperation::Custom(op), _) => Err(EvalError::UnknownOperator(op.symbol().to_owned())),
+            (UnaryOperation::Not, Value::Bool(arg)) => Ok(Value::Bool(!arg)),
+            (UnaryOperation::Not, _) => Err(EvalError::NotABool),
+            (_, Value::Bool(_)) => Err(EvalError::NotANumber),
+            (op, Value::Int(arg)) => match op.apply_int(arg, overflow) {
+                #[cfg(feature = "bigint")]
+                Err(EvalError::Overflow) if overflow == OverflowPolicy::Promote => {
+                    op.apply_big(arg.into()).map(Value::normalized)
+                }
+                res => res.map(Value::Int),
+            },
+            #[cfg(feature = "bigint")]
+            (op, Value::Big(arg)) => op.apply_big(arg).map(Value::normalized),
+            (UnaryOperation::BitNot, Value::Float(_)) => Err(EvalError::BitwiseOnFloat),
+            (op, Value::Float(arg)) => op.apply(arg).map(Value::Float),
+        }
+    }
+}
+
+/// Integers are compared exactly, other numbers as `f64`,
+/// booleans are not comparable to numbers
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Int(lhs), Value::Int(rhs)) => lhs.partial_cmp(rhs),
+            #[cfg(feature = "bigint")]
+            (Value::Big(lhs), Value::Big(rhs)) => lhs.partial_cmp(rhs),
+            #[cfg(feature = "bigint")]
+            (Value::Big(lhs), Value::Int(rhs)) => lhs.partial_cmp(&BigInt::from(*rhs)),
+            #[cfg(feature = "bigint")]
+            (Value::Int(lhs), Value::Big(rhs)) => BigInt::from(*lhs).partial_cmp(rhs),
+            (Value::Bool(lhs), Value::Bool(rhs)) => lhs.partial_cmp(rhs),
+            (Value::Bool(_), _) | (_, Value::Bool(_)) => None,
+            (lhs, rhs) => lhs.as_f64().partial_cmp(&rhs.as_f64()),
+        }
+    }
+}
+
+impl From<Number> for Value {
+    fn from(num: Number) -> Self {
+        match num {
+            Number::Int(num) => Value::Int(num),
+            Number::Float(num) => Value::Float(num),
+            #[cfg(feature = "bigint")]
+            Number::Big(num) => Value::normalized(num),
+        }
+    }
+}
+
+impl From<Int> for Value {
+    fn from(num: Int) -> Self {
+        Value::Int(num)
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl From<BigInt> for Value {
+    fn from(num: BigInt) -> Self {
+        Value::normalized(num)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(num: f64) -> Self {
+        Value::Float(num)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(flag: bool) -> Self {
+        Value::Bool(flag)
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(num) => f.write_fmt(format_args!("{num}")),
+            Value::Float(num) => f.write_fmt(format_args!("{num:?}")),
+            Value::Bool(flag) => f.write_fmt(format_args!("{flag}")),
+            #[cfg(feature = "bigint")]
+            Value::Big(num) => f.write_fmt(format_args!("{num}")),
+        }
+    }
+}
+
+impl Value {
+    /// Only integers are printed in the base, the rest as with [`Display`]
+    fn fmt_radix(&self, f: &mut std::fmt::Formatter<'_>, fmt_int: FmtInt) -> std::fmt::Result {
+        match self {
+            Value::Int(num) => fmt_int(IntRef::Int(num), f),
+            #[cfg(feature = "bigint")]
+            Value::Big(num) => fmt_int(IntRef::Big(num), f),
+            _ => Display::fmt(self, f),
+        }
+    }
+}
+
+tokens::radix_fmt!(Value);